@@ -1,10 +1,10 @@
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose, Engine};
 use clap::Parser;
-use dialoguer::{theme::ColorfulTheme, Select};
+use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
 use gdal::vector::LayerAccess;
 use gdal::{Dataset, Metadata};
-use image::{DynamicImage, Rgb, RgbImage};
+use image::{DynamicImage, Pixel, Rgb, RgbImage, Rgba, RgbaImage};
 use std::io::Write;
 use std::path::PathBuf;
 use std::process::Command;
@@ -13,7 +13,7 @@ use viuer::Config;
 // Maximum pixels to load (to avoid OOM on large rasters)
 const MAX_PIXELS: usize = 4000 * 4000;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(name = "gis-view")]
 #[command(about = "View GIS raster images in the terminal")]
 #[command(version)]
@@ -49,18 +49,174 @@ struct Args {
     #[arg(short, long)]
     interactive: bool,
 
-    /// Layer index for vector files (0-based, default: 0)
+    /// Layer(s) for vector files: 0-based index, list (e.g. "0,2,3"), or "all" (default: 0)
     #[arg(short = 'l', long)]
-    layer: Option<usize>,
+    layer: Option<String>,
 
-    /// Force display protocol: kitty, iterm, or blocks (auto-detected by default)
+    /// Force display protocol: kitty, iterm, blocks, quadrant, sextant, braille
+    /// or ascii (auto-detected by default)
     #[arg(short = 'p', long)]
     protocol: Option<String>,
+
+    /// Terminal colors for text output: 16, 256, or truecolor (auto-detected by default).
+    /// Applies to the text renderers and legends; kitty and iterm images are always full color
+    #[arg(long)]
+    colors: Option<String>,
+
+    /// Polygon fill color as #rrggbb (default: same as the outline)
+    #[arg(long)]
+    fill_color: Option<String>,
+
+    /// Polygon fill opacity from 0 (outline only) to 1
+    #[arg(long, default_value = "0.35")]
+    fill_opacity: f64,
+
+    /// Color vector features by an attribute field
+    #[arg(long, value_name = "FIELD")]
+    color_by: Option<String>,
+
+    /// Classification for numeric --color-by fields: quantile or equal
+    #[arg(long, default_value = "quantile")]
+    classify: String,
+
+    /// Number of classes for numeric --color-by fields
+    #[arg(long, default_value = "5")]
+    classes: usize,
+
+    /// Attribute filter for vector layers (OGR SQL WHERE clause, e.g. "population > 10000")
+    #[arg(long = "where", value_name = "EXPR")]
+    where_clause: Option<String>,
+
+    /// Spatial filter for vector layers: minx,miny,maxx,maxy in the layer CRS
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    bbox: Option<Vec<f64>>,
+
+    /// SQL query to render instead of a layer (e.g. "SELECT * FROM roads WHERE lanes > 2")
+    #[arg(long)]
+    sql: Option<String>,
+
+    /// SQL dialect for --sql: ogrsql or sqlite (default: the driver's own)
+    #[arg(long)]
+    dialect: Option<String>,
+
+    /// Print the attribute table of a vector layer instead of rendering it
+    #[arg(long)]
+    table: bool,
+
+    /// Maximum number of features in --table output
+    #[arg(long, default_value = "50")]
+    limit: usize,
+
+    /// Number of features to skip in --table output
+    #[arg(long, default_value = "0")]
+    offset: usize,
+
+    /// Fields to show in --table output (comma-separated, default: all)
+    #[arg(long, value_delimiter = ',')]
+    fields: Option<Vec<String>>,
+
+    /// Write the --table rows to a .csv or .json file
+    #[arg(long, value_name = "FILE")]
+    export: Option<PathBuf>,
+
+    /// Vector file to draw on top of a raster (uses --layer, --where, --bbox, --color-by)
+    #[arg(long, value_name = "FILE")]
+    overlay: Option<PathBuf>,
+
+    /// Reproject to this CRS before drawing (e.g. EPSG:3857, a PROJ string or WKT)
+    #[arg(long, value_name = "SRS")]
+    t_srs: Option<String>,
+
+    /// Draw lat/lon data and non-square pixels as stored, without correcting the aspect ratio
+    #[arg(long)]
+    no_aspect_correction: bool,
+
+    /// Line and outline width in pixels
+    #[arg(long, default_value = "1")]
+    line_width: f64,
+
+    /// Draw anti-aliased lines
+    #[arg(long)]
+    antialias: bool,
+
+    /// Don't print legends and colorbars under the image
+    #[arg(long)]
+    no_legend: bool,
+
+    /// Draw a graticule with coordinate labels, a scale bar and a north arrow
+    #[arg(long)]
+    decorations: bool,
+
+    /// Render point layers as a density heatmap instead of symbols
+    #[arg(long)]
+    density: bool,
+
+    /// Gaussian kernel standard deviation for --density, in output pixels (0: raw counts)
+    #[arg(long, default_value = "0")]
+    kernel: f64,
+
+    /// Color --density on a logarithmic scale
+    #[arg(long)]
+    log_scale: bool,
+
+    /// Simplification tolerance for vector geometries, in output pixels (0 to disable)
+    #[arg(long, default_value = "0.5")]
+    simplify: f64,
+
+    /// Label features with the values of an attribute field
+    #[arg(long, value_name = "FIELD")]
+    label: Option<String>,
+
+    /// Point symbol: square, circle, cross or triangle
+    #[arg(long, default_value = "square")]
+    symbol: String,
+
+    /// Point symbol radius in pixels
+    #[arg(long, default_value = "1")]
+    point_size: u32,
+
+    /// Point color as #rrggbb (default: the layer color)
+    #[arg(long)]
+    point_color: Option<String>,
+
+    /// Scale point symbols by a numeric field (area proportional to the value)
+    #[arg(long, value_name = "FIELD")]
+    size_by: Option<String>,
+
+    /// Radius in pixels of the largest --size-by symbol
+    #[arg(long, default_value = "12")]
+    max_point_size: u32,
+
+    /// Background color as #rrggbb for nodata and empty areas (default: transparent
+    /// where the terminal supports it)
+    #[arg(long)]
+    background: Option<String>,
+
+    /// Color for raster nodata pixels as #rrggbb (default: transparent)
+    #[arg(long)]
+    nodata_color: Option<String>,
+
+    /// Apply band scale/offset metadata so values are physical units (default)
+    #[arg(long, overrides_with = "no_unscale")]
+    unscale: bool,
+
+    /// Keep raw digital numbers even where bands have scale/offset metadata
+    #[arg(long, overrides_with = "unscale")]
+    no_unscale: bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    // Have GDAL hand out curved geometries (CircularString, CompoundCurve,
+    // CurvePolygon, MultiSurface...) as their linear approximations. The flag
+    // is process-wide: OGR checks it whenever a layer reports its geometry
+    // type or returns a feature, so --info and --table also show the linear
+    // types (a CircularString layer is listed as LineString).
+    // SAFETY: the call only sets a process-wide OGR flag; it takes no
+    // pointers and runs before any other thread or GDAL object exists.
+    unsafe { gdal_sys::OGRSetNonLinearGeometriesEnabledFlag(0) };
+
     // Check if file exists before trying to open with GDAL
     // Skip check for subdataset paths (contain ':' like SENTINEL2_L2A:/vsizip/...)
     let path_str_check = args.file.to_string_lossy();
@@ -80,7 +236,7 @@ fn main() -> Result<()> {
 
     let dataset = if is_zip {
         Dataset::open(path_str.as_ref())
-            .or_else(|_| Dataset::open(&format!("/vsizip/{}", path_str)))
+            .or_else(|_| Dataset::open(format!("/vsizip/{}", path_str)))
             .with_context(|| {
                 format!(
                     "Failed to open: {}\nThe file exists but GDAL cannot read it. The ZIP may be corrupted or incomplete.\nTry: python3 -c \"import zipfile; zipfile.ZipFile('{}')\"",
@@ -120,40 +276,63 @@ fn main() -> Result<()> {
     let layer_count = dataset.layer_count();
     if band_count == 0 && layer_count > 0 {
         // This is a vector file
+        if let Some(query) = &args.sql {
+            // Render the query result set like any other layer
+            let mut result = execute_sql_query(&dataset, query, &args)?;
+            if args.table {
+                return show_attribute_table(&mut result, &args);
+            }
+            if args.info {
+                println!("SQL: {}\n", query);
+                print_layer_info("Result", &result);
+                return Ok(());
+            }
+
+            let (img, legends) = render_vector(&mut [&mut *result], &args)?;
+            display_image(&img, &args)?;
+            print_legends(&legends, &args)?;
+            return Ok(());
+        }
+
         if args.info {
             print_vector_info(&dataset)?;
             return Ok(());
         }
 
-        // Determine which layer to render
-        let layer_idx = if let Some(idx) = args.layer {
-            if idx >= layer_count {
-                anyhow::bail!(
-                    "Layer index {} out of range. File has {} layers (0-{})",
-                    idx,
-                    layer_count,
-                    layer_count - 1
-                );
-            }
-            idx
+        // Determine which layers to render
+        let layer_indices = if let Some(selection) = &args.layer {
+            parse_layer_selection(selection, layer_count)?
         } else if layer_count > 1 && args.interactive {
             // Interactive layer selection
-            select_vector_layer(&dataset)?
+            select_vector_layers(&dataset)?
         } else if layer_count > 1 {
             // Multiple layers, no selection - show info and prompt
             eprintln!(
-                "Vector file has {} layers. Use --layer N or -i for interactive selection.\n",
+                "Vector file has {} layers. Use --layer N (or N,M / all) or -i for interactive selection.\n",
                 layer_count
             );
             print_vector_info(&dataset)?;
             return Ok(());
         } else {
-            0 // Single layer, use it
+            vec![0] // Single layer, use it
         };
 
+        let mut layers = layer_indices
+            .iter()
+            .map(|&i| dataset.layer(i))
+            .collect::<gdal::errors::Result<Vec<_>>>()?;
+        if args.table || (args.interactive && select_vector_view()? == VectorView::Table) {
+            if layers.len() > 1 {
+                anyhow::bail!("The attribute table shows one layer at a time. Use --layer N");
+            }
+            return show_attribute_table(&mut layers[0], &args);
+        }
+
         // Render vector to image
-        let img = render_vector(&dataset, layer_idx, &args)?;
+        let mut layer_refs: Vec<&mut gdal::vector::Layer> = layers.iter_mut().collect();
+        let (img, legends) = render_vector(&mut layer_refs, &args)?;
         display_image(&img, &args)?;
+        print_legends(&legends, &args)?;
         return Ok(());
     }
 
@@ -169,8 +348,34 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let img = render_raster(&dataset, &args)?;
-    display_image(&img, &args)?;
+    show_raster(&dataset, &args)
+}
+
+/// Render a raster with its overlay and decorations, display it and print
+/// its legends.
+fn show_raster(dataset: &Dataset, args: &Args) -> Result<()> {
+    // Keep the source dataset open while the warped VRT reads from it
+    let warped = match target_srs(args)? {
+        Some(srs) => Some(warp_raster(dataset, &srs)?),
+        None => None,
+    };
+    let dataset = warped.as_ref().unwrap_or(dataset);
+
+    let (img, raster_legend) = render_raster(dataset, args)?;
+    let (img, overlay_legends) = match &args.overlay {
+        Some(path) => draw_overlay(img, dataset, path, args)?,
+        None => (img, Vec::new()),
+    };
+    let legends: Vec<Legend> = std::iter::once(raster_legend)
+        .chain(overlay_legends)
+        .collect();
+    let img = if args.decorations {
+        draw_raster_decorations(img, dataset)
+    } else {
+        img
+    };
+    display_image(&img, args)?;
+    print_legends(&legends, args)?;
 
     Ok(())
 }
@@ -296,20 +501,12 @@ fn run_interactive(args: &Args) -> Result<()> {
     let modified_args = Args {
         file: PathBuf::from(&selected_path),
         bands: Some(bands),
-        width: args.width,
-        height: args.height,
         info: false,
-        stretch: args.stretch,
-        max_res: args.max_res,
         interactive: false,
-        layer: None,
-        protocol: args.protocol.clone(),
+        ..args.clone()
     };
 
-    let img = render_raster(&dataset, &modified_args)?;
-    display_image(&img, &modified_args)?;
-
-    Ok(())
+    show_raster(&dataset, &modified_args)
 }
 
 fn get_subdatasets(dataset: &Dataset) -> Vec<(String, String)> {
@@ -387,6 +584,13 @@ fn print_metadata(dataset: &Dataset) -> Result<()> {
             if let Some(nd) = nodata {
                 print!(" (nodata: {})", nd);
             }
+            if band.scale().is_some() || band.offset().is_some() {
+                print!(
+                    " (scale: {}, offset: {})",
+                    band.scale().unwrap_or(1.0),
+                    band.offset().unwrap_or(0.0)
+                );
+            }
             println!();
         }
     }
@@ -396,73 +600,293 @@ fn print_metadata(dataset: &Dataset) -> Result<()> {
 
 /// Get terminal pixel dimensions (width, height) if available
 fn get_terminal_pixel_size() -> Option<(usize, usize)> {
-    // Try Kitty's method
-    if std::env::var("KITTY_WINDOW_ID").is_ok() {
-        if let Ok(output) = Command::new("kitten")
-            .args(["icat", "--print-window-size"])
-            .output()
-        {
-            if output.status.success() {
-                let size_str = String::from_utf8_lossy(&output.stdout);
-                let parts: Vec<&str> = size_str.trim().split('x').collect();
-                if parts.len() == 2 {
-                    if let (Ok(w), Ok(h)) = (parts[0].parse(), parts[1].parse()) {
-                        return Some((w, h));
-                    }
-                }
-            }
+    // Ask the kernel first: the terminal reports its pixel size along with
+    // the cell grid (not all terminals fill it in)
+    let winsize = query_winsize();
+    if let Some((_, _, width, height)) = winsize {
+        if width > 0 && height > 0 {
+            return Some((width, height));
         }
     }
 
-    // Fallback: estimate from terminal character dimensions
-    // Most terminals are roughly 80 columns, assume ~10 pixels per character
-    if let Ok(output) = Command::new("tput").arg("cols").output() {
-        if output.status.success() {
-            if let Ok(cols) = String::from_utf8_lossy(&output.stdout)
-                .trim()
-                .parse::<usize>()
-            {
-                // Estimate pixel width (most fonts are ~8-10 pixels wide)
-                let estimated_width = cols * 9;
-                let estimated_height = estimated_width * 3 / 4; // Assume 4:3 aspect
-                return Some((estimated_width, estimated_height));
-            }
+    let cells = winsize
+        .map(|(cols, rows, _, _)| (cols, rows))
+        .or_else(tput_size);
+    if let Some((cols, rows)) = cells {
+        // Ask the terminal itself: CSI 14 t (window size), CSI 16 t (cell size)
+        if let Some(size) = query_pixel_size_escapes(cols, rows) {
+            return Some(size);
         }
+
+        // Fallback: estimate from terminal character dimensions
+        // Most fonts are roughly 9x18 pixels per cell
+        return Some((cols * 9, rows * 18));
     }
 
     // Final fallback: assume reasonable defaults
     Some((1920, 1080))
 }
 
-fn render_raster(dataset: &Dataset, args: &Args) -> Result<DynamicImage> {
+/// Pixel box the rendered image should fit into: the terminal window, or
+/// the --width/--height cell counts converted to pixels.
+fn display_target_size(args: &Args) -> (usize, usize) {
+    let (cols, rows) = query_winsize()
+        .map(|(cols, rows, _, _)| (cols, rows))
+        .or_else(tput_size)
+        .unwrap_or((80, 24));
+
+    let unicode_mode = args.protocol.as_deref().and_then(UnicodeMode::from_name);
+    let (cell_w, cell_h) = if let Some(mode) = unicode_mode {
+        mode.cell_pixels()
+    } else if args.protocol.as_deref() == Some("blocks") {
+        // Half-blocks show one pixel across and two down per cell
+        (1, 2)
+    } else {
+        let (px_w, px_h) = get_terminal_pixel_size().unwrap_or((cols * 9, rows * 18));
+        ((px_w / cols).max(1), (px_h / rows).max(1))
+    };
+
+    let width = args.width.map(|w| w as usize).unwrap_or(cols);
+    // Leave room for the status line and the shell prompt
+    let height = args
+        .height
+        .map(|h| h as usize)
+        .unwrap_or(rows.saturating_sub(2).max(1));
+
+    (width.max(1) * cell_w, height.max(1) * cell_h)
+}
+
+/// Largest size with the aspect ratio of `src_w`x`src_h` that fits the box.
+fn fit_to_box(src_w: f64, src_h: f64, box_w: usize, box_h: usize) -> (usize, usize) {
+    // Nothing to keep the aspect ratio of; fill the box
+    if !(src_w > 0.0 && src_h > 0.0 && src_w.is_finite() && src_h.is_finite()) {
+        return (box_w.max(1), box_h.max(1));
+    }
+    let scale = (box_w as f64 / src_w).min(box_h as f64 / src_h);
+    (
+        ((src_w * scale) as usize).clamp(1, box_w.max(1)),
+        ((src_h * scale) as usize).clamp(1, box_h.max(1)),
+    )
+}
+
+/// Terminal size from the TIOCGWINSZ ioctl: (cols, rows, width_px, height_px).
+#[cfg(unix)]
+fn query_winsize() -> Option<(usize, usize, usize, usize)> {
+    use std::os::unix::io::AsRawFd;
+
+    let tty = std::fs::File::open("/dev/tty").ok();
+    let fds = [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO]
+        .into_iter()
+        .chain(tty.as_ref().map(|t| t.as_raw_fd()));
+
+    for fd in fds {
+        let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass
+        let ret = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) };
+        if ret == 0 && ws.ws_col > 0 && ws.ws_row > 0 {
+            return Some((
+                ws.ws_col as usize,
+                ws.ws_row as usize,
+                ws.ws_xpixel as usize,
+                ws.ws_ypixel as usize,
+            ));
+        }
+    }
+
+    None
+}
+
+#[cfg(not(unix))]
+fn query_winsize() -> Option<(usize, usize, usize, usize)> {
+    None
+}
+
+/// Terminal size in cells from `tput cols` / `tput lines`.
+fn tput_size() -> Option<(usize, usize)> {
+    let tput = |arg: &str| -> Option<usize> {
+        let output = Command::new("tput").arg(arg).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    };
+
+    Some((tput("cols")?, tput("lines")?))
+}
+
+// How long to wait for the terminal to answer size queries
+const TERMINAL_QUERY_TIMEOUT_MS: i32 = 300;
+
+/// Query the pixel size with xterm window-ops escapes. The window size
+/// (CSI 14 t) is preferred; otherwise the cell size (CSI 16 t) is
+/// multiplied by the grid size. A trailing DA1 query (CSI c), which every
+/// terminal answers, tells us when to stop waiting.
+#[cfg(unix)]
+fn query_pixel_size_escapes(cols: usize, rows: usize) -> Option<(usize, usize)> {
+    use std::io::{IsTerminal, Read};
+    use std::os::unix::io::AsRawFd;
+
+    // Output is going to a file or pipe: no terminal image to size
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+
+    // Switch to non-canonical, no-echo mode so the reply isn't shown
+    let mut orig: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: fd is an open tty and orig is a valid termios to fill
+    if unsafe { libc::tcgetattr(fd, &mut orig) } != 0 {
+        return None;
+    }
+    let mut raw = orig;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    raw.c_cc[libc::VMIN] = 0;
+    raw.c_cc[libc::VTIME] = 0;
+    // SAFETY: raw is a copy of the current settings with flags adjusted
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return None;
+    }
+
+    let mut response = Vec::new();
+    if tty.write_all(b"\x1b[14t\x1b[16t\x1b[c").is_ok() && tty.flush().is_ok() {
+        let deadline = std::time::Instant::now()
+            + std::time::Duration::from_millis(TERMINAL_QUERY_TIMEOUT_MS as u64);
+        let mut buf = [0u8; 256];
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let mut pfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: pfd points to a single valid pollfd
+            let ready = unsafe { libc::poll(&mut pfd, 1, remaining.as_millis() as i32) };
+            if ready <= 0 {
+                break;
+            }
+            match tty.read(&mut buf) {
+                Ok(n) if n > 0 => response.extend_from_slice(&buf[..n]),
+                _ => break,
+            }
+            if has_da1_reply(&response) {
+                break;
+            }
+        }
+    }
+
+    // Drop replies that arrived after the timeout, so they don't end up in
+    // the shell's input once we exit
+    // SAFETY: fd is an open tty; restoring the settings read above
+    unsafe {
+        libc::tcflush(fd, libc::TCIFLUSH);
+        libc::tcsetattr(fd, libc::TCSANOW, &orig);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    if let Some(size) = parse_size_report(&response, "4") {
+        return Some(size);
+    }
+    parse_size_report(&response, "6").map(|(cw, ch)| (cw * cols, ch * rows))
+}
+
+#[cfg(not(unix))]
+fn query_pixel_size_escapes(_cols: usize, _rows: usize) -> Option<(usize, usize)> {
+    None
+}
+
+/// DA1 reply: ESC [ ? ... c
+fn has_da1_reply(response: &[u8]) -> bool {
+    response
+        .windows(3)
+        .position(|w| w == b"\x1b[?")
+        .is_some_and(|start| response[start..].contains(&b'c'))
+}
+
+/// Parse an xterm size report `ESC [ <code> ; height ; width t` as
+/// (width, height). Code 4 is the window in pixels, 6 a cell in pixels.
+fn parse_size_report(response: &str, code: &str) -> Option<(usize, usize)> {
+    response
+        .split('\x1b')
+        .filter_map(|r| r.strip_prefix('['))
+        .filter_map(|r| r.split_once('t').map(|(body, _)| body))
+        .find_map(|body| {
+            let mut parts = body.split(';');
+            if parts.next()? != code {
+                return None;
+            }
+            let h: usize = parts.next()?.parse().ok()?;
+            let w: usize = parts.next()?.parse().ok()?;
+            (w > 0 && h > 0 && parts.next().is_none()).then_some((w, h))
+        })
+}
+
+/// Render the selected bands, returning the image and a legend for it: the
+/// stretch range as a colorbar, or the palette entries shown.
+fn render_raster(dataset: &Dataset, args: &Args) -> Result<(DynamicImage, Legend)> {
     let (src_width, src_height) = dataset.raster_size();
     let band_count = dataset.raster_count();
 
-    // Calculate output dimensions (downsample if needed)
-    let (out_width, out_height) = if args.max_res > 0 {
-        let max_dim = args.max_res;
-        let scale = (max_dim as f64 / src_width.max(src_height) as f64).min(1.0);
-        let out_w = ((src_width as f64 * scale) as usize).max(1);
-        let out_h = ((src_height as f64 * scale) as usize).max(1);
-        (out_w, out_h)
+    // Natural size with square ground pixels: shrink the longer pixel side
+    // rather than upsampling the other
+    let aspect = if args.no_aspect_correction {
+        1.0
     } else {
-        // Full resolution, but cap at MAX_PIXELS
-        let total = src_width * src_height;
-        if total > MAX_PIXELS {
-            let scale = (MAX_PIXELS as f64 / total as f64).sqrt();
-            (
-                ((src_width as f64 * scale) as usize).max(1),
-                ((src_height as f64 * scale) as usize).max(1),
-            )
-        } else {
-            (src_width, src_height)
-        }
+        raster_pixel_aspect(dataset)
+    };
+    let (natural_width, natural_height) = if aspect >= 1.0 {
+        (src_width as f64, src_height as f64 / aspect)
+    } else {
+        (src_width as f64 * aspect, src_height as f64)
     };
+    let natural = (
+        (natural_width.round() as usize).max(1),
+        (natural_height.round() as usize).max(1),
+    );
+
+    // Calculate output dimensions: never read more pixels than the terminal
+    // can show (or than --width/--height allow)
+    let (out_width, out_height) =
+        if args.max_res > 0 || args.width.is_some() || args.height.is_some() {
+            let (box_w, box_h) = display_target_size(args);
+            let max_dim = if args.max_res > 0 {
+                args.max_res
+            } else {
+                usize::MAX
+            };
+            let (box_w, box_h) = (box_w.min(max_dim), box_h.min(max_dim));
+            if natural.0 <= box_w && natural.1 <= box_h {
+                natural
+            } else {
+                fit_to_box(natural_width, natural_height, box_w, box_h)
+            }
+        } else {
+            // Full resolution, but cap at MAX_PIXELS
+            let total = natural.0 * natural.1;
+            if total > MAX_PIXELS {
+                let scale = (MAX_PIXELS as f64 / total as f64).sqrt();
+                (
+                    ((natural_width * scale) as usize).max(1),
+                    ((natural_height * scale) as usize).max(1),
+                )
+            } else {
+                natural
+            }
+        };
 
-    if out_width != src_width || out_height != src_height {
+    // Aspect correction alone resamples without losing detail
+    if out_width < natural.0 || out_height < natural.1 {
         eprintln!(
             "Downsampling {}x{} -> {}x{} for display",
-            src_width, src_height, out_width, out_height
+            natural.0, natural.1, out_width, out_height
         );
     }
 
@@ -475,41 +899,249 @@ fn render_raster(dataset: &Dataset, args: &Args) -> Result<DynamicImage> {
     };
 
     // Read bands with GDAL-side resampling
-    let red = read_band_resampled(
+    let mut red = read_band_resampled(
         dataset, bands[0], src_width, src_height, out_width, out_height,
     )?;
-    let green = read_band_resampled(
+    let mut green = read_band_resampled(
         dataset, bands[1], src_width, src_height, out_width, out_height,
     )?;
-    let blue = read_band_resampled(
+    let mut blue = read_band_resampled(
         dataset, bands[2], src_width, src_height, out_width, out_height,
     )?;
 
-    // Get nodata value from first band
-    let nodata = dataset.rasterband(bands[0])?.no_data_value();
+    // Nodata, mask and alpha bands of every selected band combined
+    let valid = read_validity_mask(
+        dataset,
+        &bands,
+        &[&red, &green, &blue],
+        (src_width, src_height),
+        (out_width, out_height),
+    )?;
+    let nodata_color = args
+        .nodata_color
+        .as_deref()
+        .map(parse_hex_color)
+        .transpose()?;
+
+    // A single palette band is drawn with its own colors
+    if bands[0] == bands[1] && bands[1] == bands[2] {
+        let band = dataset.rasterband(bands[0])?;
+        let table = band.color_table();
+        if let Some(table) = &table {
+            return Ok(render_palette(
+                &red,
+                &valid,
+                table,
+                nodata_color,
+                (out_width, out_height),
+                bands[0],
+            ));
+        }
+    }
+
+    // Stretch physical values rather than digital numbers; nodata was
+    // matched against the raw values above
+    // The two flags override each other, so at most one of them is set
+    let unscale = args.unscale || !args.no_unscale;
+    if unscale {
+        for (&band_idx, values) in bands.iter().zip([&mut red, &mut green, &mut blue]) {
+            unscale_band(dataset, band_idx, values)?;
+        }
+    }
 
     // Normalize bands using shared min/max to preserve color relationships
     let stretch = args.stretch / 100.0;
     let (global_min, global_max) =
-        compute_global_percentiles(&red, &green, &blue, nodata, stretch, 1.0 - stretch);
-    let red_norm = normalize_with_range(&red, nodata, global_min, global_max);
-    let green_norm = normalize_with_range(&green, nodata, global_min, global_max);
-    let blue_norm = normalize_with_range(&blue, nodata, global_min, global_max);
+        compute_global_percentiles(&red, &green, &blue, &valid, stretch, 1.0 - stretch);
+    let red_norm = normalize_with_range(&red, &valid, global_min, global_max);
+    let green_norm = normalize_with_range(&green, &valid, global_min, global_max);
+    let blue_norm = normalize_with_range(&blue, &valid, global_min, global_max);
 
-    // Create RGB image
-    let mut img = RgbImage::new(out_width as u32, out_height as u32);
+    // Create RGBA image; invalid pixels stay transparent unless colored, and
+    // partially transparent ones are composited over the nodata color
+    let mut img = match nodata_color {
+        Some(color) => RgbaImage::from_pixel(out_width as u32, out_height as u32, color.to_rgba()),
+        None => RgbaImage::new(out_width as u32, out_height as u32),
+    };
     for y in 0..out_height {
         for x in 0..out_width {
             let idx = y * out_width + x;
-            img.put_pixel(
-                x as u32,
-                y as u32,
-                image::Rgb([red_norm[idx], green_norm[idx], blue_norm[idx]]),
-            );
+            if valid[idx] > 0 {
+                let color = Rgb([red_norm[idx], green_norm[idx], blue_norm[idx]]);
+                blend_pixel(
+                    &mut img,
+                    x as u32,
+                    y as u32,
+                    color,
+                    valid[idx] as f64 / 255.0,
+                );
+            }
+        }
+    }
+
+    // Every band is stretched over the same range, so one gray ramp
+    // explains all channels
+    let title = if bands[0] == bands[1] && bands[1] == bands[2] {
+        format!("Band {}", bands[0])
+    } else {
+        format!(
+            "Bands {},{},{} (shared stretch)",
+            bands[0], bands[1], bands[2]
+        )
+    };
+    let entries = (0..5)
+        .map(|i| {
+            let t = i as f64 / 4.0;
+            let gray = (t * 255.0).round() as u8;
+            (
+                format_value(global_min + t * (global_max - global_min)),
+                Rgb([gray, gray, gray]),
+            )
+        })
+        .collect();
+    let legend = Legend {
+        title,
+        entries,
+        continuous: true,
+    };
+
+    Ok((DynamicImage::ImageRgba8(img), legend))
+}
+
+/// Most palette entries listed in a raster legend.
+const MAX_PALETTE_LEGEND: usize = 16;
+
+/// Color a palette band through its color table. The legend lists the
+/// values present, most frequent first.
+fn render_palette(
+    values: &[f64],
+    valid: &[u8],
+    table: &gdal::raster::ColorTable,
+    nodata_color: Option<Rgb<u8>>,
+    (width, height): (usize, usize),
+    band: usize,
+) -> (DynamicImage, Legend) {
+    let mut img = RgbaImage::new(width as u32, height as u32);
+    let mut counts: std::collections::HashMap<i64, (usize, Rgb<u8>)> =
+        std::collections::HashMap::new();
+
+    for (idx, &v) in values.iter().enumerate() {
+        let (x, y) = ((idx % width) as u32, (idx / width) as u32);
+        if valid[idx] == 0 || v < 0.0 {
+            if let Some(color) = nodata_color {
+                img.put_pixel(x, y, color.to_rgba());
+            }
+            continue;
         }
+        let Some(entry) = table.entry_as_rgb(v as usize) else {
+            continue;
+        };
+        let color = Rgb([entry.r, entry.g, entry.b].map(|c| c.clamp(0, 255) as u8));
+        img.put_pixel(
+            x,
+            y,
+            Rgba([
+                color.0[0],
+                color.0[1],
+                color.0[2],
+                entry.a.clamp(0, 255) as u8,
+            ]),
+        );
+        counts.entry(v as i64).or_insert((0, color)).0 += 1;
+    }
+
+    let mut present: Vec<(i64, (usize, Rgb<u8>))> = counts.into_iter().collect();
+    present.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+    let shown = present.len().min(MAX_PALETTE_LEGEND);
+    let mut entries: Vec<(String, Rgb<u8>)> = present[..shown]
+        .iter()
+        .map(|(value, (_, color))| (value.to_string(), *color))
+        .collect();
+    if present.len() > shown {
+        entries.push((
+            format!("... {} more values", present.len() - shown),
+            OTHER_COLOR,
+        ));
+    }
+
+    let legend = Legend {
+        title: format!("Band {} palette", band),
+        entries,
+        continuous: false,
+    };
+    (DynamicImage::ImageRgba8(img), legend)
+}
+
+/// Parse --t-srs into a spatial reference with x = easting/longitude.
+fn target_srs(args: &Args) -> Result<Option<gdal::spatial_ref::SpatialRef>> {
+    let Some(definition) = &args.t_srs else {
+        return Ok(None);
+    };
+    let mut srs = gdal::spatial_ref::SpatialRef::from_definition(definition)
+        .with_context(|| format!("Invalid --t-srs: {}", definition))?;
+    srs.set_axis_mapping_strategy(gdal::spatial_ref::AxisMappingStrategy::TraditionalGisOrder);
+    Ok(Some(srs))
+}
+
+/// Wrap a raster in a warped VRT in the target CRS. Pixels are only warped
+/// when read, so the usual resampled reads stay cheap. The VRT reads through
+/// the source handle, so `dataset` must stay open until the returned dataset
+/// is dropped.
+fn warp_raster(dataset: &Dataset, srs: &gdal::spatial_ref::SpatialRef) -> Result<Dataset> {
+    if dataset.spatial_ref().is_err() {
+        anyhow::bail!("Raster has no CRS, cannot reproject it with --t-srs");
+    }
+    let wkt = std::ffi::CString::new(srs.to_wkt()?)?;
+
+    // Source CRS (null) is taken from the dataset
+    // SAFETY: the source handle is valid for the duration of the call, the
+    // WKT is a NUL-terminated string that outlives it, and null warp options
+    // are allowed. GDAL doesn't take ownership of the source.
+    let c_dataset = unsafe {
+        gdal_sys::GDALAutoCreateWarpedVRT(
+            dataset.c_dataset(),
+            std::ptr::null(),
+            wkt.as_ptr(),
+            gdal_sys::GDALResampleAlg::GRA_Bilinear,
+            0.125,
+            std::ptr::null(),
+        )
+    };
+    if c_dataset.is_null() {
+        anyhow::bail!("GDAL could not reproject the raster to the --t-srs CRS");
     }
 
-    Ok(DynamicImage::ImageRgb8(img))
+    // SAFETY: the handle is non-null and owned by nobody else, so the Dataset
+    // takes ownership and closes it on drop. Closing the VRT doesn't close the
+    // source; callers declare the VRT after the source, so it is dropped
+    // first and never reads from a closed source.
+    Ok(unsafe { Dataset::from_c_dataset(c_dataset) })
+}
+
+/// Ground width over ground height of one raster pixel: the pixel size ratio
+/// from the geotransform, times cos(latitude) for geographic CRSs.
+fn raster_pixel_aspect(dataset: &Dataset) -> f64 {
+    let Ok(gt) = dataset.geo_transform() else {
+        return 1.0;
+    };
+    let pixel_w = gt[1].hypot(gt[4]);
+    let pixel_h = gt[2].hypot(gt[5]);
+    if pixel_w == 0.0 || pixel_h == 0.0 {
+        return 1.0;
+    }
+
+    let (width, height) = dataset.raster_size();
+    let center_y = gt[3] + width as f64 / 2.0 * gt[4] + height as f64 / 2.0 * gt[5];
+    pixel_w / pixel_h * longitude_scale(dataset.spatial_ref().ok().as_ref(), center_y)
+}
+
+/// Length of a degree of longitude relative to a degree of latitude at `lat`
+/// for geographic CRSs; 1 for projected or unknown CRSs.
+fn longitude_scale(srs: Option<&gdal::spatial_ref::SpatialRef>, lat: f64) -> f64 {
+    match srs {
+        Some(srs) if srs.is_geographic() => lat.clamp(-90.0, 90.0).to_radians().cos().max(0.01),
+        _ => 1.0,
+    }
 }
 
 fn read_band_resampled(
@@ -539,30 +1171,98 @@ fn read_band_resampled(
     Ok(data)
 }
 
+/// Apply the band's scale and offset metadata (value * scale + offset).
+/// Bands without it are left untouched.
+fn unscale_band(dataset: &Dataset, band_idx: usize, values: &mut [f64]) -> Result<()> {
+    let band = dataset
+        .rasterband(band_idx)
+        .with_context(|| format!("Failed to read band {}", band_idx))?;
+    let scale = band.scale().unwrap_or(1.0);
+    let offset = band.offset().unwrap_or(0.0);
+    if scale == 1.0 && offset == 0.0 {
+        return Ok(());
+    }
+    for v in values.iter_mut() {
+        *v = *v * scale + offset;
+    }
+    Ok(())
+}
+
+/// Combine the validity of every selected band into one mask (0 invalid,
+/// 255 fully valid). A pixel is invalid where any band holds its nodata value
+/// or a non-finite value; GDAL mask bands (internal masks, per-dataset masks,
+/// alpha bands) are read at the output size and can only lower the result.
+fn read_validity_mask(
+    dataset: &Dataset,
+    bands: &[usize],
+    values: &[&[f64]],
+    (src_width, src_height): (usize, usize),
+    (out_width, out_height): (usize, usize),
+) -> Result<Vec<u8>> {
+    let mut valid = vec![255u8; out_width * out_height];
+    let mut dataset_mask_read = false;
+
+    for (i, (&band_idx, data)) in bands.iter().zip(values).enumerate() {
+        if bands[..i].contains(&band_idx) {
+            continue;
+        }
+        let band = dataset
+            .rasterband(band_idx)
+            .with_context(|| format!("Failed to read band {}", band_idx))?;
+
+        let nodata = band.no_data_value();
+        for (m, &v) in valid.iter_mut().zip(data.iter()) {
+            if !v.is_finite() || nodata.is_some_and(|nd| (v - nd).abs() < f64::EPSILON) {
+                *m = 0;
+            }
+        }
+
+        // Nodata masks were handled from the values above
+        let flags = band.mask_flags()?;
+        if flags.is_all_valid() || flags.is_nodata() {
+            continue;
+        }
+        if flags.is_per_dataset() {
+            if dataset_mask_read {
+                continue;
+            }
+            dataset_mask_read = true;
+        }
+
+        let mask = band
+            .open_mask_band()
+            .and_then(|mask| {
+                mask.read_as::<u8>(
+                    (0, 0),
+                    (src_width, src_height),
+                    (out_width, out_height),
+                    None,
+                )
+            })
+            .with_context(|| format!("Failed to read the mask of band {}", band_idx))?;
+        for (m, &a) in valid.iter_mut().zip(mask.data()) {
+            *m = (*m).min(a);
+        }
+    }
+
+    Ok(valid)
+}
+
 /// Compute global min/max percentiles across all three bands.
 /// Using the same range for all bands preserves color relationships.
 fn compute_global_percentiles(
     r: &[f64],
     g: &[f64],
     b: &[f64],
-    nodata: Option<f64>,
+    valid: &[u8],
     low_pct: f64,
     high_pct: f64,
 ) -> (f64, f64) {
-    let is_valid = |v: &f64| -> bool {
-        if let Some(nd) = nodata {
-            (v - nd).abs() > f64::EPSILON && v.is_finite()
-        } else {
-            v.is_finite()
-        }
-    };
-
-    let mut all_valid: Vec<f64> = r
+    let mut all_valid: Vec<f64> = [r, g, b]
         .iter()
-        .chain(g.iter())
-        .chain(b.iter())
-        .filter(|v| is_valid(v))
-        .copied()
+        .flat_map(|band| band.iter().zip(valid))
+        .filter(|(_, &m)| m > 0)
+        .map(|(&v, _)| v)
         .collect();
 
     if all_valid.is_empty() {
@@ -578,22 +1278,13 @@ fn compute_global_percentiles(
 }
 
 /// Normalize values to 0-255 using a pre-computed min/max range.
-fn normalize_with_range(
-    values: &[f64],
-    nodata: Option<f64>,
-    min_val: f64,
-    max_val: f64,
-) -> Vec<u8> {
+fn normalize_with_range(values: &[f64], valid: &[u8], min_val: f64, max_val: f64) -> Vec<u8> {
     let range = max_val - min_val;
     values
         .iter()
-        .map(|&v| {
-            if let Some(nd) = nodata {
-                if (v - nd).abs() < f64::EPSILON {
-                    return 0u8;
-                }
-            }
-            if !v.is_finite() {
+        .zip(valid)
+        .map(|(&v, &m)| {
+            if m == 0 {
                 return 0u8;
             }
             if range > 0.0 {
@@ -605,36 +1296,99 @@ fn normalize_with_range(
         .collect()
 }
 
+/// Canvas color for transparent areas when the output can't show them.
+const DEFAULT_BACKGROUND: Rgb<u8> = Rgb([20, 20, 30]);
+
+/// Composite the image over an opaque background color.
+fn flatten(img: &DynamicImage, background: Rgb<u8>) -> RgbImage {
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let px = rgba.get_pixel(x, y).0;
+        let alpha = px[3] as f64 / 255.0;
+        Rgb(std::array::from_fn(|c| {
+            (px[c] as f64 * alpha + background.0[c] as f64 * (1.0 - alpha)).round() as u8
+        }))
+    })
+}
+
 fn display_image(img: &DynamicImage, args: &Args) -> Result<()> {
-    match args.protocol.as_deref() {
-        Some("kitty") => display_kitty_direct(img),
+    // An explicit background replaces transparency in every output
+    let flattened;
+    let img = match &args.background {
+        Some(color) => {
+            flattened = DynamicImage::ImageRgb8(flatten(img, parse_hex_color(color)?));
+            &flattened
+        }
+        None => img,
+    };
+    let depth = color_depth(args)?;
+    let mux = detect_multiplexer();
+
+    match args.protocol.as_deref() {
+        Some("kitty") => display_kitty_direct(img, mux),
+        // viuer writes straight to stdout, so inside a multiplexer we emit the
+        // iTerm2 sequence ourselves to be able to wrap it
+        Some("iterm") if mux.is_some() => display_iterm_direct(img, mux),
         Some("iterm") => {
             let config = Config {
                 absolute_offset: false,
                 use_kitty: false,
                 use_iterm: true,
+                width: args.width,
+                height: args.height,
+                transparent: true,
                 ..Default::default()
             };
             viuer::print(img, &config).context("Failed to display image")?;
             Ok(())
         }
+        // viuer has no 16-color mode and doesn't dither, so use our own half-blocks
+        Some("blocks") if depth != ColorDepth::TrueColor => {
+            display_unicode(img, UnicodeMode::HalfBlock, depth, args)
+        }
         Some("blocks") => {
             let config = Config {
                 absolute_offset: false,
                 use_kitty: false,
                 use_iterm: false,
+                width: args.width,
+                height: args.height,
+                transparent: true,
                 ..Default::default()
             };
             viuer::print(img, &config).context("Failed to display image")?;
             Ok(())
         }
-        Some(other) => anyhow::bail!("Unknown protocol '{}'. Use: kitty, iterm, or blocks", other),
+        Some(name) => match UnicodeMode::from_name(name) {
+            Some(mode) => display_unicode(img, mode, depth, args),
+            None => anyhow::bail!(
+                "Unknown protocol '{}'. Use: kitty, iterm, blocks, quadrant, sextant, braille, or ascii",
+                name
+            ),
+        },
+        // Inside tmux/screen TERM no longer identifies the outer terminal,
+        // so fall back to the variables inherited from it
+        None if mux.is_some() && std::env::var("KITTY_WINDOW_ID").is_ok() => {
+            display_kitty_direct(img, mux)
+        }
+        None if mux.is_some() && std::env::var("LC_TERMINAL").is_ok_and(|t| t == "iTerm2") => {
+            display_iterm_direct(img, mux)
+        }
+        None if depth != ColorDepth::TrueColor
+            && viuer::get_kitty_support() == viuer::KittySupport::None
+            && !viuer::is_iterm_supported() =>
+        {
+            display_unicode(img, UnicodeMode::HalfBlock, depth, args)
+        }
         None => {
             // Auto-detect
             let config = Config {
                 absolute_offset: false,
                 use_kitty: true,
                 use_iterm: true,
+                width: args.width,
+                height: args.height,
+                transparent: true,
                 ..Default::default()
             };
             viuer::print(img, &config).context("Failed to display image")?;
@@ -643,9 +1397,93 @@ fn display_image(img: &DynamicImage, args: &Args) -> Result<()> {
     }
 }
 
+/// Terminal multiplexer the output is going through, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+fn detect_multiplexer() -> Option<Multiplexer> {
+    if std::env::var("TMUX").is_ok_and(|v| !v.is_empty()) {
+        Some(Multiplexer::Tmux)
+    } else if std::env::var("STY").is_ok_and(|v| !v.is_empty()) {
+        Some(Multiplexer::Screen)
+    } else {
+        None
+    }
+}
+
+/// Wrap a graphics escape sequence (Kitty, iTerm2, sixel) in the DCS
+/// passthrough envelope so the multiplexer forwards it to the outer terminal.
+fn wrap_passthrough(seq: &str, mux: Option<Multiplexer>) -> String {
+    match mux {
+        None => seq.to_string(),
+        // tmux: ESC P tmux; <sequence with every ESC doubled> ESC \
+        Some(Multiplexer::Tmux) => {
+            format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+        }
+        // screen truncates long DCS strings, so split into short envelopes.
+        // An inner ST (ESC \) would close the envelope early, so envelopes
+        // are cut between its two bytes: screen passes a trailing ESC through
+        // and the terminal reassembles the ST from consecutive envelopes.
+        Some(Multiplexer::Screen) => {
+            let bytes = seq.as_bytes();
+            let mut out = String::with_capacity(seq.len() + seq.len() / 64);
+            let mut start = 0;
+            while start < bytes.len() {
+                let mut end = (start + SCREEN_DCS_CHUNK).min(bytes.len());
+                if let Some(pos) = bytes[start..end].windows(2).position(|w| w == b"\x1b\\") {
+                    end = start + pos + 1;
+                }
+                out.push_str("\x1bP");
+                // Sequences are ASCII (escapes + base64), so byte chunks are valid UTF-8
+                out.push_str(std::str::from_utf8(&bytes[start..end]).unwrap());
+                out.push_str("\x1b\\");
+                start = end;
+            }
+            out
+        }
+    }
+}
+
+// GNU screen's maximum DCS string length
+const SCREEN_DCS_CHUNK: usize = 768;
+
+/// tmux 3.3+ drops passthrough sequences unless `allow-passthrough` is on.
+/// Older versions don't have the option and always pass them through. The
+/// check is a best guess, so it only warns.
+fn warn_tmux_passthrough() {
+    // The value the current pane uses (including inherited ones), then the
+    // pane and global values for tmux versions without -A
+    let value = ["-Apv", "-pv", "-gv"].iter().find_map(|flags| {
+        let output = Command::new("tmux")
+            .args(["show-options", flags, "allow-passthrough"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let value = stdout.lines().next()?.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    });
+
+    if value.as_deref() == Some("off") {
+        eprintln!(
+            "Warning: tmux seems to block graphics passthrough (allow-passthrough is off).\n\
+             Enable it with:\n  tmux set -g allow-passthrough on\n\
+             or add 'set -g allow-passthrough on' to ~/.tmux.conf.\n\
+             Alternatively use: gis-view --protocol blocks <file>"
+        );
+    }
+}
+
 /// Send image directly using Kitty graphics protocol escape sequences.
 /// Bypasses viuer's terminal detection which fails over SSH.
-fn display_kitty_direct(img: &DynamicImage) -> Result<()> {
+fn display_kitty_direct(img: &DynamicImage, mux: Option<Multiplexer>) -> Result<()> {
+    if mux == Some(Multiplexer::Tmux) {
+        warn_tmux_passthrough();
+    }
+
     let rgba = img.to_rgba8();
     let width = rgba.width();
     let height = rgba.height();
@@ -664,33 +1502,517 @@ fn display_kitty_direct(img: &DynamicImage) -> Result<()> {
 
     for (i, chunk) in chunks.iter().enumerate() {
         let is_last = i == chunks.len() - 1;
-        if i == 0 {
+        let seq = if i == 0 {
             // First chunk: include image metadata
-            write!(
-                stdout,
+            format!(
                 "\x1b_Ga=T,f=32,s={},v={},m={};{}\x1b\\",
                 width,
                 height,
                 if is_last { 0 } else { 1 },
                 chunk
-            )?;
+            )
         } else {
             // Continuation chunks
-            write!(
-                stdout,
-                "\x1b_Gm={};{}\x1b\\",
-                if is_last { 0 } else { 1 },
-                chunk
-            )?;
-        }
+            format!("\x1b_Gm={};{}\x1b\\", if is_last { 0 } else { 1 }, chunk)
+        };
+        write!(stdout, "{}", wrap_passthrough(&seq, mux))?;
+    }
+
+    writeln!(stdout)?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Send image as a PNG using the iTerm2 inline image protocol (OSC 1337).
+fn display_iterm_direct(img: &DynamicImage, mux: Option<Multiplexer>) -> Result<()> {
+    if mux == Some(Multiplexer::Tmux) {
+        warn_tmux_passthrough();
     }
 
+    let mut png = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .context("Failed to encode image as PNG")?;
+
+    let encoded = general_purpose::STANDARD.encode(&png);
+    let seq = format!(
+        "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07",
+        png.len(),
+        encoded
+    );
+
+    let mut stdout = std::io::stdout().lock();
+    write!(stdout, "{}", wrap_passthrough(&seq, mux))?;
     writeln!(stdout)?;
     stdout.flush()?;
 
     Ok(())
 }
 
+/// Text-only renderers that pack several image pixels into each cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnicodeMode {
+    /// Upper half blocks (U+2580), used for the `blocks` fallback on
+    /// terminals without truecolor
+    HalfBlock,
+    /// 2x2 quadrant blocks (U+2596..U+259F)
+    Quadrant,
+    /// 2x3 sextants from Symbols for Legacy Computing (U+1FB00..U+1FB3B)
+    Sextant,
+    /// 2x4 braille dots (U+2800..U+28FF)
+    Braille,
+    /// Luminance ramp of ASCII characters, 1x2 pixels per cell
+    Ascii,
+}
+
+impl UnicodeMode {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quadrant" => Some(Self::Quadrant),
+            "sextant" => Some(Self::Sextant),
+            "braille" => Some(Self::Braille),
+            "ascii" => Some(Self::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Image pixels covered by one terminal cell (columns, rows).
+    fn cell_pixels(self) -> (usize, usize) {
+        match self {
+            Self::HalfBlock => (1, 2),
+            Self::Quadrant => (2, 2),
+            Self::Sextant => (2, 3),
+            Self::Braille => (2, 4),
+            Self::Ascii => (1, 2),
+        }
+    }
+}
+
+/// Color capability of the terminal for text output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+/// Color depth from --colors, or detected from COLORTERM and terminfo.
+fn color_depth(args: &Args) -> Result<ColorDepth> {
+    match args.colors.as_deref() {
+        Some("truecolor") | Some("24bit") => Ok(ColorDepth::TrueColor),
+        Some("256") => Ok(ColorDepth::Ansi256),
+        Some("16") => Ok(ColorDepth::Ansi16),
+        Some(other) => anyhow::bail!("Unknown color mode '{}'. Use: 16, 256, or truecolor", other),
+        None => Ok(detect_color_depth()),
+    }
+}
+
+fn detect_color_depth() -> ColorDepth {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorDepth::TrueColor;
+    }
+
+    // Ask terminfo how many colors the terminal has
+    if let Ok(output) = Command::new("tput").arg("colors").output() {
+        if output.status.success() {
+            if let Ok(colors) = String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .parse::<i32>()
+            {
+                return match colors {
+                    c if c >= 16_777_216 => ColorDepth::TrueColor,
+                    c if c >= 256 => ColorDepth::Ansi256,
+                    _ => ColorDepth::Ansi16,
+                };
+            }
+        }
+    }
+
+    match std::env::var("TERM") {
+        Ok(term) if term.contains("direct") => ColorDepth::TrueColor,
+        Ok(term) if term.contains("256color") => ColorDepth::Ansi256,
+        _ => ColorDepth::Ansi16,
+    }
+}
+
+/// A glyph with its foreground and background colors.
+type Cell = (char, Option<[u8; 3]>, Option<[u8; 3]>);
+
+/// Render the image as colored Unicode/ASCII characters. Each cell picks the
+/// glyph whose foreground/background split best matches its pixels.
+fn display_unicode(
+    img: &DynamicImage,
+    mode: UnicodeMode,
+    depth: ColorDepth,
+    args: &Args,
+) -> Result<()> {
+    let (cell_w, cell_h) = mode.cell_pixels();
+    let (term_cols, term_rows) = query_winsize()
+        .map(|(cols, rows, _, _)| (cols, rows))
+        .or_else(tput_size)
+        .unwrap_or((80, 24));
+    let max_cols = args.width.map(|w| w as usize).unwrap_or(term_cols).max(1);
+    let max_rows = args
+        .height
+        .map(|h| h as usize)
+        .unwrap_or(term_rows.saturating_sub(2))
+        .max(1);
+
+    // Cells are about twice as tall as they are wide
+    let (cols, rows) = fit_to_box(
+        img.width() as f64,
+        img.height() as f64 / 2.0,
+        max_cols,
+        max_rows,
+    );
+    // Character cells can't show through to the terminal, so transparent
+    // areas take the default background
+    let mut rgb = flatten(
+        &img.resize_exact(
+            (cols * cell_w) as u32,
+            (rows * cell_h) as u32,
+            image::imageops::FilterType::Triangle,
+        ),
+        DEFAULT_BACKGROUND,
+    );
+    // Half-blocks map one pixel to each color, so dither the pixels; the other
+    // modes average pixels into cell colors, which would wash the dither out
+    if depth != ColorDepth::TrueColor && mode == UnicodeMode::HalfBlock {
+        rgb = dither_to_palette(&rgb, depth);
+    }
+
+    let mut cells: Vec<Cell> = Vec::with_capacity(cols * rows);
+    let mut pixels = Vec::with_capacity(cell_w * cell_h);
+    for row in 0..rows {
+        for col in 0..cols {
+            pixels.clear();
+            for dy in 0..cell_h {
+                for dx in 0..cell_w {
+                    let px = rgb.get_pixel((col * cell_w + dx) as u32, (row * cell_h + dy) as u32);
+                    pixels.push(px.0);
+                }
+            }
+
+            let (glyph, fg, bg) = match mode {
+                UnicodeMode::HalfBlock => ('▀', Some(pixels[0]), Some(pixels[1])),
+                UnicodeMode::Ascii => {
+                    let avg = average_color(&pixels);
+                    (ascii_glyph(luminance(avg)), Some(avg), None)
+                }
+                _ => {
+                    let (mask, fg, bg) = split_cell(&pixels);
+                    let glyph = match mode {
+                        UnicodeMode::Quadrant => quadrant_glyph(mask),
+                        UnicodeMode::Sextant => sextant_glyph(mask),
+                        _ => braille_glyph(mask),
+                    };
+                    (glyph, Some(fg), Some(bg))
+                }
+            };
+            cells.push((glyph, fg, bg));
+        }
+    }
+
+    // Dither the foreground and background colors at cell resolution
+    if depth != ColorDepth::TrueColor && mode != UnicodeMode::HalfBlock {
+        for background in [false, true] {
+            let color_of = |cell: &Cell| {
+                if background {
+                    cell.2
+                } else {
+                    cell.1
+                }
+            };
+            if cells.iter().all(|cell| color_of(cell).is_none()) {
+                continue;
+            }
+            let plane = RgbImage::from_fn(cols as u32, rows as u32, |x, y| {
+                Rgb(color_of(&cells[y as usize * cols + x as usize]).unwrap_or([0, 0, 0]))
+            });
+            let dithered = dither_to_palette(&plane, depth);
+            for (cell, px) in cells.iter_mut().zip(dithered.pixels()) {
+                let slot = if background { &mut cell.2 } else { &mut cell.1 };
+                if slot.is_some() {
+                    *slot = Some(px.0);
+                }
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(cols * rows * 24);
+    for cell_row in cells.chunks(cols) {
+        let mut last: (Option<[u8; 3]>, Option<[u8; 3]>) = (None, None);
+        for &(glyph, fg, bg) in cell_row {
+            if fg != last.0 {
+                if let Some(c) = fg {
+                    out.push_str(&color_escape(c, depth, false));
+                }
+            }
+            if bg != last.1 {
+                if let Some(c) = bg {
+                    out.push_str(&color_escape(c, depth, true));
+                }
+            }
+            last = (fg, bg);
+            out.push(glyph);
+        }
+        out.push_str("\x1b[0m\n");
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(out.as_bytes())?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+fn luminance(c: [u8; 3]) -> f64 {
+    0.299 * c[0] as f64 + 0.587 * c[1] as f64 + 0.114 * c[2] as f64
+}
+
+fn average_color(pixels: &[[u8; 3]]) -> [u8; 3] {
+    if pixels.is_empty() {
+        return [0, 0, 0];
+    }
+    let mut sum = [0u32; 3];
+    for p in pixels {
+        for (s, &v) in sum.iter_mut().zip(p) {
+            *s += v as u32;
+        }
+    }
+    let n = pixels.len() as u32;
+    [(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8]
+}
+
+/// Split a cell's pixels into a foreground set (brighter than the mean) and
+/// a background set. Returns (bitmask of foreground pixels, fg, bg).
+fn split_cell(pixels: &[[u8; 3]]) -> (u32, [u8; 3], [u8; 3]) {
+    let lums: Vec<f64> = pixels.iter().map(|&p| luminance(p)).collect();
+    let min = lums.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = lums.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+    // Flat cell: a plain background is the closest match
+    if max - min < 8.0 {
+        let avg = average_color(pixels);
+        return (0, avg, avg);
+    }
+
+    let threshold = (min + max) / 2.0;
+    let mut mask = 0u32;
+    let mut fg_pixels = Vec::new();
+    let mut bg_pixels = Vec::new();
+    for (i, (&p, &l)) in pixels.iter().zip(&lums).enumerate() {
+        if l > threshold {
+            mask |= 1 << i;
+            fg_pixels.push(p);
+        } else {
+            bg_pixels.push(p);
+        }
+    }
+
+    (mask, average_color(&fg_pixels), average_color(&bg_pixels))
+}
+
+/// Mask bits: 0 = top-left, 1 = top-right, 2 = bottom-left, 3 = bottom-right.
+fn quadrant_glyph(mask: u32) -> char {
+    const QUADRANTS: [char; 16] = [
+        ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+    ];
+    QUADRANTS[(mask & 0xf) as usize]
+}
+
+/// Mask bits are row-major over the 2x3 cell, as in the Unicode sextant names.
+fn sextant_glyph(mask: u32) -> char {
+    let mask = mask & 0x3f;
+    match mask {
+        0 => ' ',
+        0x15 => '▌',
+        0x2a => '▐',
+        0x3f => '█',
+        // The block skips the patterns that already exist as half blocks
+        m => {
+            let skipped = (m > 0x15) as u32 + (m > 0x2a) as u32;
+            char::from_u32(0x1FB00 + m - 1 - skipped).unwrap_or('?')
+        }
+    }
+}
+
+/// Mask bits are row-major over the 2x4 cell; braille numbers dots by column.
+fn braille_glyph(mask: u32) -> char {
+    const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+    let bits = DOTS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .fold(0, |acc, (_, &dot)| acc | dot);
+    char::from_u32(0x2800 + bits).unwrap_or(' ')
+}
+
+fn ascii_glyph(lum: f64) -> char {
+    const RAMP: &[u8] = b" .:-=+*#%@";
+    let idx = ((lum / 256.0) * RAMP.len() as f64) as usize;
+    RAMP[idx.min(RAMP.len() - 1)] as char
+}
+
+/// SGR escape setting the foreground or background color.
+fn color_escape(c: [u8; 3], depth: ColorDepth, background: bool) -> String {
+    match depth {
+        ColorDepth::TrueColor => format!(
+            "\x1b[{};2;{};{};{}m",
+            if background { 48 } else { 38 },
+            c[0],
+            c[1],
+            c[2]
+        ),
+        ColorDepth::Ansi256 => format!(
+            "\x1b[{};5;{}m",
+            if background { 48 } else { 38 },
+            nearest_xterm256(c)
+        ),
+        ColorDepth::Ansi16 => {
+            let idx = nearest_ansi16(c) as u8;
+            let code = match (idx < 8, background) {
+                (true, false) => 30 + idx,
+                (false, false) => 90 + idx - 8,
+                (true, true) => 40 + idx,
+                (false, true) => 100 + idx - 8,
+            };
+            format!("\x1b[{}m", code)
+        }
+    }
+}
+
+// Standard xterm values for the 16 ANSI colors
+const ANSI16_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+// Channel levels of the xterm 6x6x6 color cube (indices 16-231)
+const XTERM_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// RGB value of an xterm-256 palette index.
+fn xterm256_color(idx: u8) -> [u8; 3] {
+    match idx {
+        0..=15 => ANSI16_PALETTE[idx as usize],
+        16..=231 => {
+            let i = (idx - 16) as usize;
+            [
+                XTERM_CUBE_LEVELS[i / 36],
+                XTERM_CUBE_LEVELS[(i / 6) % 6],
+                XTERM_CUBE_LEVELS[i % 6],
+            ]
+        }
+        _ => [8 + (idx - 232) * 10; 3],
+    }
+}
+
+/// Quantize to the terminal palette with Floyd-Steinberg error diffusion, so
+/// smooth gradients don't band into flat patches.
+fn dither_to_palette(img: &RgbImage, depth: ColorDepth) -> RgbImage {
+    let quantize = |c: [u8; 3]| -> [u8; 3] {
+        match depth {
+            ColorDepth::TrueColor => c,
+            ColorDepth::Ansi256 => xterm256_color(nearest_xterm256(c)),
+            ColorDepth::Ansi16 => ANSI16_PALETTE[nearest_ansi16(c)],
+        }
+    };
+
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut work: Vec<[f32; 3]> = img
+        .pixels()
+        .map(|p| [p.0[0] as f32, p.0[1] as f32, p.0[2] as f32])
+        .collect();
+    let mut out = RgbImage::new(img.width(), img.height());
+
+    for y in 0..height {
+        for x in 0..width {
+            let old = work[y * width + x];
+            let clamped = old.map(|v| v.round().clamp(0.0, 255.0) as u8);
+            let new = quantize(clamped);
+            out.put_pixel(x as u32, y as u32, Rgb(new));
+
+            let err = [
+                old[0] - new[0] as f32,
+                old[1] - new[1] as f32,
+                old[2] - new[2] as f32,
+            ];
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let nx = x as isize + dx;
+                let ny = y + dy;
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    let cell = &mut work[ny * width + nx as usize];
+                    for c in 0..3 {
+                        cell[c] += err[c] * weight;
+                    }
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+
+    out
+}
+
+fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(&b)
+        .map(|(&x, &y)| (x as i32 - y as i32).pow(2) as u32)
+        .sum()
+}
+
+fn nearest_ansi16(c: [u8; 3]) -> usize {
+    (0..16)
+        .min_by_key(|&i| color_distance(c, ANSI16_PALETTE[i]))
+        .unwrap_or(0)
+}
+
+/// Nearest xterm-256 index, choosing between the color cube and the gray ramp.
+fn nearest_xterm256(c: [u8; 3]) -> u8 {
+    let level = |v: u8| -> usize {
+        (0..6)
+            .min_by_key(|&i| (XTERM_CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (level(c[0]), level(c[1]), level(c[2]));
+    let cube_idx = 16 + 36 * r + 6 * g + b;
+    let cube_color = [
+        XTERM_CUBE_LEVELS[r],
+        XTERM_CUBE_LEVELS[g],
+        XTERM_CUBE_LEVELS[b],
+    ];
+
+    // Gray ramp 232-255: 8, 18, ..., 238
+    let avg = (c[0] as u32 + c[1] as u32 + c[2] as u32) / 3;
+    let gray_step = ((avg.saturating_sub(3)) / 10).min(23);
+    let gray_value = (8 + gray_step * 10) as u8;
+    let gray_idx = 232 + gray_step;
+
+    if color_distance(c, [gray_value; 3]) < color_distance(c, cube_color) {
+        gray_idx as u8
+    } else {
+        cube_idx as u8
+    }
+}
+
 fn print_vector_info(dataset: &Dataset) -> Result<()> {
     let layer_count = dataset.layer_count();
     let driver = dataset.driver().short_name();
@@ -701,47 +2023,98 @@ fn print_vector_info(dataset: &Dataset) -> Result<()> {
 
     for i in 0..layer_count {
         let layer = dataset.layer(i)?;
-        let name = layer.name();
-        let feature_count = layer.feature_count();
-        let geom_type = layer
-            .defn()
-            .geom_fields()
-            .next()
-            .map(|f| format!("{:?}", f.field_type()))
-            .unwrap_or_else(|| "Unknown".to_string());
+        print_layer_info(&format!("Layer [{}]", i), &layer);
+    }
 
-        println!("Layer [{}]: {}", i, name);
-        println!("  Features: {}", feature_count);
-        println!("  Geometry: {}", geom_type);
+    Ok(())
+}
 
-        // Get extent
-        if let Ok(extent) = layer.get_extent() {
-            println!(
-                "  Extent: ({:.6}, {:.6}) - ({:.6}, {:.6})",
-                extent.MinX, extent.MinY, extent.MaxX, extent.MaxY
-            );
-        }
+fn print_layer_info(heading: &str, layer: &gdal::vector::Layer) {
+    let name = layer.name();
+    let feature_count = layer.feature_count();
+    let geom_type = layer
+        .defn()
+        .geom_fields()
+        .next()
+        .map(|f| format!("{:?}", f.field_type()))
+        .unwrap_or_else(|| "Unknown".to_string());
 
-        // Get spatial reference
-        if let Some(srs) = layer.spatial_ref() {
-            if let Some(name) = srs.name() {
-                println!("  CRS: {}", name);
-            }
-        }
+    println!("{}: {}", heading, name);
+    println!("  Features: {}", feature_count);
+    println!("  Geometry: {}", geom_type);
+
+    // Get extent
+    if let Ok(extent) = layer.get_extent() {
+        println!(
+            "  Extent: ({:.6}, {:.6}) - ({:.6}, {:.6})",
+            extent.MinX, extent.MinY, extent.MaxX, extent.MaxY
+        );
+    }
 
-        // List attribute fields
-        let defn = layer.defn();
-        let fields: Vec<String> = defn.fields().map(|f| f.name()).collect();
-        if !fields.is_empty() {
-            println!("  Fields: {}", fields.join(", "));
+    // Get spatial reference
+    if let Some(srs) = layer.spatial_ref() {
+        if let Some(name) = srs.name() {
+            println!("  CRS: {}", name);
         }
-        println!();
     }
 
-    Ok(())
+    // List attribute fields
+    let defn = layer.defn();
+    let fields: Vec<String> = defn.fields().map(|f| f.name()).collect();
+    if !fields.is_empty() {
+        println!("  Fields: {}", fields.join(", "));
+    }
+    println!();
+}
+
+/// Run --sql against the dataset with the --dialect of choice.
+fn execute_sql_query<'a>(
+    dataset: &'a Dataset,
+    query: &str,
+    args: &Args,
+) -> Result<gdal::vector::sql::ResultSet<'a>> {
+    use gdal::vector::sql::Dialect;
+
+    let dialect = match args.dialect.as_deref() {
+        None => Dialect::DEFAULT,
+        Some("ogrsql") => Dialect::OGR,
+        Some("sqlite") => Dialect::SQLITE,
+        Some(other) => anyhow::bail!("Unknown SQL dialect '{}'. Use: ogrsql or sqlite", other),
+    };
+
+    dataset
+        .execute_sql(query, None, dialect)
+        .with_context(|| format!("SQL query failed: {}", query))?
+        .with_context(|| format!("SQL query returned no result set: {}", query))
+}
+
+/// Parse --layer: a single index, a comma-separated list, or "all".
+fn parse_layer_selection(selection: &str, layer_count: usize) -> Result<Vec<usize>> {
+    if selection.trim().eq_ignore_ascii_case("all") {
+        return Ok((0..layer_count).collect());
+    }
+
+    selection
+        .split(',')
+        .map(|part| {
+            let idx: usize = part
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid layer '{}'. Use N, N,M,... or all", part))?;
+            if idx >= layer_count {
+                anyhow::bail!(
+                    "Layer index {} out of range. File has {} layers (0-{})",
+                    idx,
+                    layer_count,
+                    layer_count - 1
+                );
+            }
+            Ok(idx)
+        })
+        .collect()
 }
 
-fn select_vector_layer(dataset: &Dataset) -> Result<usize> {
+fn select_vector_layers(dataset: &Dataset) -> Result<Vec<usize>> {
     let layer_count = dataset.layer_count();
     let mut layer_items = Vec::new();
 
@@ -761,164 +2134,2753 @@ fn select_vector_layer(dataset: &Dataset) -> Result<usize> {
         ));
     }
 
-    let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select layer to display")
+    let mut defaults = vec![false; layer_count];
+    defaults[0] = true;
+    let selection = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select layers to display (space to toggle, enter to confirm)")
         .items(&layer_items)
-        .default(0)
+        .defaults(&defaults)
         .interact()?;
 
+    if selection.is_empty() {
+        anyhow::bail!("No layers selected");
+    }
+
     Ok(selection)
 }
 
-fn render_vector(dataset: &Dataset, layer_idx: usize, args: &Args) -> Result<DynamicImage> {
-    // Get terminal size for output dimensions
-    let terminal_size = get_terminal_pixel_size();
-    let (img_width, _) = terminal_size.unwrap_or((1024, 768));
-
-    // Use specified max_res or terminal size
-    let max_dim = if args.max_res > 0 && args.max_res < img_width {
-        args.max_res
-    } else {
-        img_width
-    };
-
-    // Get the selected layer
-    let layer = dataset.layer(layer_idx)?;
-    let extent = layer
-        .get_extent()
-        .context("Cannot get layer extent for rendering")?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VectorView {
+    Map,
+    Table,
+}
 
-    // Calculate output dimensions maintaining aspect ratio
-    let extent_width = extent.MaxX - extent.MinX;
-    let extent_height = extent.MaxY - extent.MinY;
-    let aspect = extent_width / extent_height;
+fn select_vector_view() -> Result<VectorView> {
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select view")
+        .items(&["Render map", "Attribute table"])
+        .default(0)
+        .interact()?;
 
-    let (out_width, out_height) = if aspect > 1.0 {
-        (max_dim, (max_dim as f64 / aspect) as usize)
+    Ok(if selection == 1 {
+        VectorView::Table
     } else {
-        ((max_dim as f64 * aspect) as usize, max_dim)
-    };
+        VectorView::Map
+    })
+}
 
-    eprintln!(
-        "Rendering {} features to {}x{} image",
-        layer.feature_count(),
-        out_width,
-        out_height
-    );
+/// Rows of a vector layer read for --table.
+struct AttributeTable {
+    /// Field name, type name and whether the field is numeric
+    columns: Vec<(String, String, bool)>,
+    rows: Vec<TableRow>,
+}
+
+struct TableRow {
+    fid: Option<u64>,
+    geom_type: Option<String>,
+    /// Vertices over all parts and rings
+    point_count: usize,
+    /// Only built for --export; the terminal table shows type and size
+    wkt: Option<String>,
+    values: Vec<Option<gdal::vector::FieldValue>>,
+}
 
-    // Create image with dark background
-    let mut img = RgbImage::from_pixel(out_width as u32, out_height as u32, Rgb([20, 20, 30]));
+// Widest a column gets in the terminal table before values are truncated
+const MAX_COLUMN_WIDTH: usize = 30;
 
-    // Transform coordinates to pixel space
-    let scale_x = out_width as f64 / extent_width;
-    let scale_y = out_height as f64 / extent_height;
+fn show_attribute_table(layer: &mut gdal::vector::Layer, args: &Args) -> Result<()> {
+    let table = read_attribute_table(layer, args)?;
 
-    // Draw each feature
-    let mut layer = dataset.layer(layer_idx)?; // Re-get layer to reset iterator
-    for feature in layer.features() {
-        if let Some(geom) = feature.geometry() {
-            draw_geometry(&mut img, &geom, &extent, scale_x, scale_y, out_height);
-        }
+    if let Some(path) = &args.export {
+        export_attribute_table(&table, path)?;
+        eprintln!("Wrote {} rows to {}", table.rows.len(), path.display());
+        return Ok(());
     }
 
-    Ok(DynamicImage::ImageRgb8(img))
+    print_attribute_table(&table);
+    Ok(())
 }
 
-fn draw_geometry(
-    img: &mut RgbImage,
-    geom: &gdal::vector::Geometry,
-    extent: &gdal::vector::Envelope,
-    scale_x: f64,
-    scale_y: f64,
-    img_height: usize,
-) {
-    let color = Rgb([100, 200, 255]); // Cyan-ish color for vectors
+fn read_attribute_table(layer: &mut gdal::vector::Layer, args: &Args) -> Result<AttributeTable> {
+    use gdal::vector::OGRFieldType;
 
-    // Get geometry type name
-    let geom_type = geom.geometry_type();
+    apply_vector_filters(layer, args)?;
 
-    match geom_type {
-        // Points
-        gdal::vector::OGRwkbGeometryType::wkbPoint
-        | gdal::vector::OGRwkbGeometryType::wkbPoint25D
-        | gdal::vector::OGRwkbGeometryType::wkbPointM
-        | gdal::vector::OGRwkbGeometryType::wkbPointZM => {
-            let (x, y, _) = geom.get_point(0);
-            let px = ((x - extent.MinX) * scale_x) as i32;
-            let py = (img_height as f64 - (y - extent.MinY) * scale_y) as i32;
-            draw_point(img, px, py, color);
-        }
-        // LineStrings
-        gdal::vector::OGRwkbGeometryType::wkbLineString
-        | gdal::vector::OGRwkbGeometryType::wkbLineString25D => {
-            draw_linestring(img, geom, extent, scale_x, scale_y, img_height, color);
-        }
-        // Polygons
-        gdal::vector::OGRwkbGeometryType::wkbPolygon
-        | gdal::vector::OGRwkbGeometryType::wkbPolygon25D => {
-            // Draw exterior ring
-            let ring = geom.get_geometry(0);
-            draw_linestring(img, &ring, extent, scale_x, scale_y, img_height, color);
-        }
-        // Multi geometries - recurse
-        gdal::vector::OGRwkbGeometryType::wkbMultiPoint
-        | gdal::vector::OGRwkbGeometryType::wkbMultiLineString
-        | gdal::vector::OGRwkbGeometryType::wkbMultiPolygon
-        | gdal::vector::OGRwkbGeometryType::wkbGeometryCollection => {
-            for i in 0..geom.geometry_count() {
-                let sub_geom = geom.get_geometry(i);
-                draw_geometry(img, &sub_geom, extent, scale_x, scale_y, img_height);
-            }
-        }
-        _ => {
-            // Try to handle as collection
-            for i in 0..geom.geometry_count() {
-                let sub_geom = geom.get_geometry(i);
-                draw_geometry(img, &sub_geom, extent, scale_x, scale_y, img_height);
+    let all_fields: Vec<(String, OGRFieldType::Type)> = layer
+        .defn()
+        .fields()
+        .map(|f| (f.name(), f.field_type()))
+        .collect();
+    let selected: Vec<usize> = match &args.fields {
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                all_fields
+                    .iter()
+                    .position(|(n, _)| n == name)
+                    .with_context(|| {
+                        let available: Vec<&str> =
+                            all_fields.iter().map(|(n, _)| n.as_str()).collect();
+                        format!(
+                            "Field '{}' not found. Available fields: {}",
+                            name,
+                            available.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?,
+        None => (0..all_fields.len()).collect(),
+    };
+
+    let columns = selected
+        .iter()
+        .map(|&i| {
+            let (name, field_type) = &all_fields[i];
+            let numeric = matches!(
+                *field_type,
+                OGRFieldType::OFTInteger | OGRFieldType::OFTInteger64 | OGRFieldType::OFTReal
+            );
+            (
+                name.clone(),
+                gdal::vector::field_type_to_name(*field_type),
+                numeric,
+            )
+        })
+        .collect();
+
+    let rows = layer
+        .features()
+        .skip(args.offset)
+        .take(args.limit)
+        .map(|feature| TableRow {
+            fid: feature.fid(),
+            geom_type: feature.geometry().map(|g| g.geometry_name()),
+            point_count: feature.geometry().map_or(0, total_point_count),
+            wkt: if args.export.is_some() {
+                feature.geometry().and_then(|g| g.wkt().ok())
+            } else {
+                None
+            },
+            values: selected
+                .iter()
+                .map(|&i| feature.field(i).ok().flatten())
+                .collect(),
+        })
+        .collect();
+
+    Ok(AttributeTable { columns, rows })
+}
+
+/// Vertices in a geometry, summed over its parts and rings.
+fn total_point_count(geom: &gdal::vector::Geometry) -> usize {
+    match geom.geometry_count() {
+        0 => geom.point_count(),
+        n => (0..n)
+            .map(|i| total_point_count(&geom.get_geometry(i)))
+            .sum(),
+    }
+}
+
+fn format_field_value(value: &gdal::vector::FieldValue) -> String {
+    use gdal::vector::FieldValue;
+
+    fn join<T: ToString>(values: &[T]) -> String {
+        values
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    match value {
+        FieldValue::IntegerValue(v) => v.to_string(),
+        FieldValue::Integer64Value(v) => v.to_string(),
+        FieldValue::RealValue(v) => v.to_string(),
+        FieldValue::StringValue(v) => v.clone(),
+        FieldValue::IntegerListValue(v) => join(v),
+        FieldValue::Integer64ListValue(v) => join(v),
+        FieldValue::RealListValue(v) => join(v),
+        FieldValue::StringListValue(v) => join(v),
+        FieldValue::DateValue(v) => v.to_string(),
+        FieldValue::DateTimeValue(v) => v.to_string(),
+    }
+}
+
+/// Cut a string to `width` characters, marking the cut with an ellipsis.
+fn truncate_cell(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else {
+        let mut out: String = s.chars().take(width.saturating_sub(1)).collect();
+        out.push('…');
+        out
+    }
+}
+
+/// Print the table with aligned columns, one terminal page at a time.
+fn print_attribute_table(table: &AttributeTable) {
+    use std::io::IsTerminal;
+
+    let mut headers = vec![("FID".to_string(), "".to_string(), true)];
+    headers.push(("Geometry".to_string(), "".to_string(), false));
+    headers.extend(table.columns.iter().cloned());
+
+    let cells: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![
+                row.fid.map(|f| f.to_string()).unwrap_or_default(),
+                match (&row.geom_type, row.point_count) {
+                    (Some(name), n) if n > 1 => format!("{} ({} pts)", name, n),
+                    (Some(name), _) => name.clone(),
+                    (None, _) => String::new(),
+                },
+            ];
+            cells.extend(row.values.iter().map(|v| match v {
+                Some(v) => format_field_value(v),
+                None => "NULL".to_string(),
+            }));
+            cells
+                .into_iter()
+                .map(|c| truncate_cell(&c.replace('\n', " "), MAX_COLUMN_WIDTH))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, (name, type_name, _))| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([name.chars().count(), type_name.chars().count()])
+                .max()
+                .unwrap_or(0)
+                .min(MAX_COLUMN_WIDTH)
+        })
+        .collect();
+
+    let format_row = |values: &[String]| -> String {
+        values
+            .iter()
+            .zip(&widths)
+            .zip(&headers)
+            .map(|((v, &w), (_, _, numeric))| {
+                let v = truncate_cell(v, w);
+                if *numeric {
+                    format!("{:>w$}", v, w = w)
+                } else {
+                    format!("{:<w$}", v, w = w)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let names: Vec<String> = headers.iter().map(|(n, _, _)| n.clone()).collect();
+    let types: Vec<String> = headers.iter().map(|(_, t, _)| t.clone()).collect();
+    let separator = widths
+        .iter()
+        .map(|&w| "-".repeat(w))
+        .collect::<Vec<_>>()
+        .join("-+-");
+
+    let print_header = || {
+        println!("{}", format_row(&names));
+        println!("{}", format_row(&types));
+        println!("{}", separator);
+    };
+
+    let interactive = std::io::stdout().is_terminal() && std::io::stdin().is_terminal();
+    let page_size = query_winsize()
+        .map(|(_, rows, _, _)| rows)
+        .or_else(|| tput_size().map(|(_, rows)| rows))
+        .unwrap_or(24)
+        .saturating_sub(5)
+        .max(1);
+
+    print_header();
+    for (i, row) in cells.iter().enumerate() {
+        if interactive && i > 0 && i % page_size == 0 {
+            eprint!(
+                "-- {}/{} rows (Enter: next page, q: quit) --",
+                i,
+                cells.len()
+            );
+            let mut input = String::new();
+            if std::io::stdin().read_line(&mut input).is_err() || input.trim() == "q" {
+                return;
+            }
+            print_header();
+        }
+        println!("{}", format_row(row));
+    }
+    println!("({} rows)", cells.len());
+}
+
+/// Write the table rows to CSV or JSON, picked from the file extension.
+fn export_attribute_table(table: &AttributeTable, path: &std::path::Path) -> Result<()> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let output = match extension.as_str() {
+        "csv" => attribute_table_csv(table),
+        "json" => attribute_table_json(table),
+        _ => anyhow::bail!(
+            "Unsupported export format for {}. Use a .csv or .json file",
+            path.display()
+        ),
+    };
+
+    std::fs::write(path, output).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn attribute_table_csv(table: &AttributeTable) -> String {
+    fn quote(s: &str) -> String {
+        if s.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    }
+
+    let mut out = String::new();
+    let header: Vec<String> = ["fid", "geometry_type", "wkt"]
+        .iter()
+        .map(|s| s.to_string())
+        .chain(table.columns.iter().map(|(name, _, _)| quote(name)))
+        .collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+
+    for row in &table.rows {
+        let line: Vec<String> = [
+            row.fid.map(|f| f.to_string()).unwrap_or_default(),
+            row.geom_type.clone().unwrap_or_default(),
+            row.wkt.clone().unwrap_or_default(),
+        ]
+        .into_iter()
+        .chain(
+            row.values
+                .iter()
+                .map(|v| v.as_ref().map(format_field_value).unwrap_or_default()),
+        )
+        .map(|v| quote(&v))
+        .collect();
+        out.push_str(&line.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn attribute_table_json(table: &AttributeTable) -> String {
+    use gdal::vector::FieldValue;
+
+    fn string(s: &str) -> String {
+        let mut out = String::from("\"");
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        out
+    }
+
+    fn number(v: f64) -> String {
+        if v.is_finite() {
+            v.to_string()
+        } else {
+            "null".to_string()
+        }
+    }
+
+    fn value(v: &Option<FieldValue>) -> String {
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        match v {
+            None => "null".to_string(),
+            Some(FieldValue::IntegerValue(v)) => v.to_string(),
+            Some(FieldValue::Integer64Value(v)) => v.to_string(),
+            Some(FieldValue::RealValue(v)) => number(*v),
+            Some(FieldValue::IntegerListValue(v)) => {
+                list(v.iter().map(|x| x.to_string()).collect())
+            }
+            Some(FieldValue::Integer64ListValue(v)) => {
+                list(v.iter().map(|x| x.to_string()).collect())
+            }
+            Some(FieldValue::RealListValue(v)) => list(v.iter().map(|&x| number(x)).collect()),
+            Some(FieldValue::StringListValue(v)) => list(v.iter().map(|x| string(x)).collect()),
+            Some(other) => string(&format_field_value(other)),
+        }
+    }
+
+    let rows: Vec<String> = table
+        .rows
+        .iter()
+        .map(|row| {
+            let mut members = vec![
+                format!(
+                    "\"fid\":{}",
+                    row.fid.map(|f| f.to_string()).unwrap_or("null".into())
+                ),
+                format!(
+                    "\"geometry_type\":{}",
+                    row.geom_type
+                        .as_deref()
+                        .map(string)
+                        .unwrap_or("null".into())
+                ),
+                format!(
+                    "\"wkt\":{}",
+                    row.wkt.as_deref().map(string).unwrap_or("null".into())
+                ),
+            ];
+            members.extend(
+                table
+                    .columns
+                    .iter()
+                    .zip(&row.values)
+                    .map(|((name, _, _), v)| format!("{}:{}", string(name), value(v))),
+            );
+            format!("  {{{}}}", members.join(","))
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+fn render_vector(
+    layers: &mut [&mut gdal::vector::Layer],
+    args: &Args,
+) -> Result<(DynamicImage, Vec<Legend>)> {
+    // Fit the output to the terminal (or --width/--height), capped by max_res
+    let (box_w, box_h) = display_target_size(args);
+    let max_dim = if args.max_res > 0 {
+        args.max_res
+    } else {
+        usize::MAX
+    };
+
+    // Union of the layer extents, in the --t-srs CRS if given and otherwise
+    // in the first layer's CRS
+    let target = target_srs(args)?;
+    let first_srs = layers.first().and_then(|l| l.spatial_ref());
+    let first_name = layers.first().map(|l| l.name()).unwrap_or_default();
+    check_field_options(&layers.iter().map(|l| &**l).collect::<Vec<_>>(), args)?;
+    let filtered = args.where_clause.is_some() || args.bbox.is_some();
+    let mut reprojections = Vec::with_capacity(layers.len());
+    let mut scans = Vec::with_capacity(layers.len());
+    let mut extent: Option<gdal::vector::Envelope> = None;
+    for layer in layers.iter_mut() {
+        apply_vector_filters(layer, args)?;
+        let reproject = match &target {
+            Some(srs) => {
+                let Some(layer_srs) = layer.spatial_ref() else {
+                    anyhow::bail!(
+                        "Layer '{}' has no CRS, cannot reproject it with --t-srs",
+                        layer.name()
+                    );
+                };
+                reprojection(Some(layer_srs), Some(srs.clone()))?
+            }
+            None => {
+                let layer_srs = layer.spatial_ref();
+                if layer_srs.is_none() != first_srs.is_none() {
+                    eprintln!(
+                        "Warning: only one of '{}' and '{}' has a CRS, drawing '{}' without reprojection",
+                        first_name,
+                        layer.name(),
+                        layer.name()
+                    );
+                }
+                reprojection(layer_srs, first_srs.clone())?
+            }
+        };
+
+        // One read of the features for the filtered extent and the
+        // --color-by/--size-by statistics; the layer header has the rest
+        let scan = scan_layer(layer, args, filtered)?;
+        let layer_extent = if filtered {
+            scan.extent
+        } else {
+            layer.get_extent().ok()
+        };
+        let layer_extent =
+            match (&reproject, layer_extent) {
+                (Some(ct), Some(e)) => Some(transform_envelope(ct, &e).with_context(|| {
+                    format!("Cannot reproject the extent of '{}'", layer.name())
+                })?),
+                (_, e) => e,
+            };
+        extent = match (extent, layer_extent) {
+            (Some(a), Some(b)) => Some(union_envelope(&a, &b)),
+            (a, b) => a.or(b),
+        };
+        reprojections.push(reproject);
+        scans.push(scan);
+    }
+    let Some(extent) = extent else {
+        if filtered {
+            anyhow::bail!("No features match the filter");
+        }
+        anyhow::bail!("Cannot get layer extent for rendering");
+    };
+    let extent = pad_degenerate_extent(extent);
+
+    // Calculate output dimensions maintaining the ground aspect ratio: a
+    // degree of longitude shrinks with cos(latitude)
+    let extent_width = extent.MaxX - extent.MinX;
+    let extent_height = extent.MaxY - extent.MinY;
+    let output_srs = target.or(first_srs);
+    let x_scale = if args.no_aspect_correction {
+        1.0
+    } else {
+        longitude_scale(output_srs.as_ref(), (extent.MinY + extent.MaxY) / 2.0)
+    };
+
+    let (out_width, out_height) = fit_to_box(
+        extent_width * x_scale,
+        extent_height,
+        box_w.min(max_dim),
+        box_h.min(max_dim),
+    );
+
+    match scans.iter().map(|s| s.feature_count).sum::<Option<u64>>() {
+        Some(count) => eprintln!(
+            "Rendering {} features to {}x{} image",
+            count, out_width, out_height
+        ),
+        None => eprintln!("Rendering to {}x{} image", out_width, out_height),
+    }
+
+    // Create image with a transparent background
+    let mut img = RgbaImage::new(out_width as u32, out_height as u32);
+
+    // Transform coordinates to pixel space
+    let transform = PixelTransform::from_extent(&extent, out_width, out_height);
+
+    if args.density {
+        let legend = draw_density(&mut img, layers, &transform, &reprojections, args)?;
+        if args.decorations {
+            draw_decorations(&mut img, &transform, output_srs.as_ref());
+        }
+        return Ok((DynamicImage::ImageRgba8(img), vec![legend]));
+    }
+
+    let base_style = vector_style(args)?;
+    let multiple = layers.len() > 1;
+    let mut legends = Vec::new();
+    let mut layer_legend = Legend {
+        title: "Layers".to_string(),
+        entries: Vec::new(),
+        continuous: false,
+    };
+
+    // Draw layers in order, so later layers end up on top
+    let mut labels = Vec::new();
+    for (i, (layer, scan)) in layers.iter_mut().zip(scans).enumerate() {
+        // Each layer gets its own color when several are drawn together
+        let style = if multiple {
+            VectorStyle {
+                line: Rgb(CATEGORY_PALETTE[i % CATEGORY_PALETTE.len()]),
+                ..base_style
+            }
+        } else {
+            base_style
+        };
+        layer_legend.entries.push((layer.name(), style.line));
+
+        let (legend, layer_labels) = draw_layer(
+            &mut img,
+            layer,
+            &transform,
+            style,
+            reprojections[i].as_ref(),
+            scan,
+            args,
+        )?;
+        if let Some(mut legend) = legend {
+            if multiple {
+                legend.title = format!("{}: {}", layer.name(), legend.title);
+            }
+            legends.push(legend);
+        }
+        labels.extend(layer_labels);
+    }
+
+    // Labels go on top of every layer
+    place_labels(&mut img, &labels);
+
+    if args.decorations {
+        draw_decorations(&mut img, &transform, output_srs.as_ref());
+    }
+
+    if multiple && args.color_by.is_none() {
+        legends.push(layer_legend);
+    }
+
+    Ok((DynamicImage::ImageRgba8(img), legends))
+}
+
+/// Draw the layers of a vector file on top of a rendered raster, placing
+/// features with the raster's geotransform and reprojecting them to the
+/// raster CRS when the two differ.
+fn draw_overlay(
+    img: DynamicImage,
+    raster: &Dataset,
+    path: &std::path::Path,
+    args: &Args,
+) -> Result<(DynamicImage, Vec<Legend>)> {
+    let gt = raster
+        .geo_transform()
+        .context("Raster has no geotransform, cannot place the overlay")?;
+    let src_size = raster.raster_size();
+    let transform = PixelTransform::from_geo_transform(
+        &gt,
+        src_size,
+        (img.width() as usize, img.height() as usize),
+    )?;
+
+    let vector = Dataset::open(path)
+        .with_context(|| format!("Failed to open overlay: {}", path.display()))?;
+    if vector.layer_count() == 0 {
+        anyhow::bail!("Overlay has no vector layers: {}", path.display());
+    }
+    let layer_indices = match &args.layer {
+        Some(selection) => parse_layer_selection(selection, vector.layer_count())?,
+        None => vec![0],
+    };
+
+    // Raster bounds in map coordinates, from its four corners
+    let (width, height) = (src_size.0 as f64, src_size.1 as f64);
+    let bounds = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+        .iter()
+        .map(|&(col, row)| {
+            (
+                gt[0] + col * gt[1] + row * gt[2],
+                gt[3] + col * gt[4] + row * gt[5],
+            )
+        })
+        .fold(
+            [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            |b, (x, y)| [b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)],
+        );
+
+    let mut style = vector_style(args)?;
+    if style.fill_color.is_none() {
+        // Outline only, so the raster stays visible underneath
+        style.fill_opacity = 0.0;
+    }
+    let multiple = layer_indices.len() > 1;
+    let mut legends = Vec::new();
+    let mut layer_legend = Legend {
+        title: "Layers".to_string(),
+        entries: Vec::new(),
+        continuous: false,
+    };
+
+    let overlay_layers = layer_indices
+        .iter()
+        .map(|&idx| vector.layer(idx))
+        .collect::<gdal::errors::Result<Vec<_>>>()?;
+    check_field_options(&overlay_layers.iter().collect::<Vec<_>>(), args)?;
+
+    let mut canvas = img.to_rgba8();
+    for (i, mut layer) in overlay_layers.into_iter().enumerate() {
+        apply_vector_filters(&mut layer, args)?;
+
+        let reproject = reprojection(layer.spatial_ref(), raster.spatial_ref().ok())?;
+        if args.bbox.is_none() {
+            // Only read features that can touch the raster
+            let inverse = reprojection(raster.spatial_ref().ok(), layer.spatial_ref())?;
+            let [min_x, min_y, max_x, max_y] = match &inverse {
+                Some(ct) => ct.transform_bounds(&bounds, 21).unwrap_or(bounds),
+                None => bounds,
+            };
+            layer.set_spatial_filter_rect(min_x, min_y, max_x, max_y);
+        }
+
+        let style = if multiple {
+            VectorStyle {
+                line: Rgb(CATEGORY_PALETTE[i % CATEGORY_PALETTE.len()]),
+                ..style
+            }
+        } else {
+            style
+        };
+        layer_legend.entries.push((layer.name(), style.line));
+
+        let scan = scan_layer(&mut layer, args, false)?;
+        match scan.feature_count {
+            Some(count) => eprintln!("Overlaying {} features from {}", count, layer.name()),
+            None => eprintln!("Overlaying {}", layer.name()),
+        }
+        // Overlays are not labelled
+        let (legend, _) = draw_layer(
+            &mut canvas,
+            &mut layer,
+            &transform,
+            style,
+            reproject.as_ref(),
+            scan,
+            args,
+        )?;
+        if let Some(mut legend) = legend {
+            if multiple {
+                legend.title = format!("{}: {}", layer.name(), legend.title);
+            }
+            legends.push(legend);
+        }
+    }
+
+    if multiple && args.color_by.is_none() {
+        legends.push(layer_legend);
+    }
+
+    Ok((DynamicImage::ImageRgba8(canvas), legends))
+}
+
+/// Bounding box of an envelope after reprojection, densified along the edges
+/// so curved edges (e.g. meridians in polar projections) are covered.
+fn transform_envelope(
+    ct: &gdal::spatial_ref::CoordTransform,
+    e: &gdal::vector::Envelope,
+) -> Result<gdal::vector::Envelope> {
+    let [min_x, min_y, max_x, max_y] =
+        ct.transform_bounds(&[e.MinX, e.MinY, e.MaxX, e.MaxY], 21)?;
+    Ok(gdal::vector::Envelope {
+        MinX: min_x,
+        MaxX: max_x,
+        MinY: min_y,
+        MaxY: max_y,
+    })
+}
+
+/// Bin the points of all layers into a grid at the output resolution,
+/// optionally smooth it with a Gaussian kernel, and color it with the ramp.
+fn draw_density(
+    img: &mut RgbaImage,
+    layers: &mut [&mut gdal::vector::Layer],
+    transform: &PixelTransform,
+    reprojections: &[Option<gdal::spatial_ref::CoordTransform>],
+    args: &Args,
+) -> Result<Legend> {
+    if !args.kernel.is_finite() || args.kernel < 0.0 {
+        anyhow::bail!("Kernel size must be 0 or positive, got {}", args.kernel);
+    }
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut grid = vec![0.0f64; width * height];
+    let mut non_points = 0u64;
+
+    for (layer, reproject) in layers.iter_mut().zip(reprojections) {
+        for feature in layer.features() {
+            let Some(geom) = feature.geometry() else {
+                continue;
+            };
+            if !is_point_geometry(geom) {
+                non_points += 1;
+                continue;
+            }
+            let reprojected;
+            let geom = match reproject {
+                Some(ct) => match geom.transform(ct) {
+                    Ok(g) => {
+                        reprojected = g;
+                        &reprojected
+                    }
+                    Err(_) => continue,
+                },
+                None => geom,
+            };
+
+            // A point, or the parts of a multipoint
+            let points: Vec<(f64, f64, f64)> = if geom.geometry_count() > 0 {
+                (0..geom.geometry_count())
+                    .map(|i| geom.get_geometry(i))
+                    .filter(|part| part.point_count() > 0)
+                    .map(|part| part.get_point(0))
+                    .collect()
+            } else if geom.point_count() > 0 {
+                vec![geom.get_point(0)]
+            } else {
+                Vec::new()
+            };
+            for (x, y, _) in points {
+                let (px, py) = transform.to_pixel(x, y);
+                // Points on the extent's edge land on (or, with rounding, just
+                // past) the image border; keep them in the edge pixels
+                let (w, h) = (width as f64, height as f64);
+                if px > -1.0 && py > -1.0 && px < w + 1.0 && py < h + 1.0 {
+                    let col = (px.max(0.0) as usize).min(width - 1);
+                    let row = (py.max(0.0) as usize).min(height - 1);
+                    grid[row * width + col] += 1.0;
+                }
+            }
+        }
+    }
+    if non_points > 0 {
+        eprintln!("--density ignored {} non-point features", non_points);
+    }
+
+    if args.kernel > 0.0 {
+        gaussian_blur(&mut grid, width, height, args.kernel);
+    }
+
+    let max = grid.iter().copied().fold(0.0, f64::max);
+    let scale = |v: f64| -> f64 {
+        if args.log_scale {
+            (1.0 + v).ln() / (1.0 + max).ln()
+        } else {
+            v / max
+        }
+    };
+    if max > 0.0 {
+        for (i, &v) in grid.iter().enumerate() {
+            if v > 0.0 {
+                let color = ramp_color(scale(v));
+                img.put_pixel((i % width) as u32, (i / width) as u32, color.to_rgba());
+            }
+        }
+    }
+
+    // Legend values at evenly spaced ramp positions
+    let entries = (0..5)
+        .map(|i| {
+            let t = i as f64 / 4.0;
+            let v = if args.log_scale {
+                (1.0 + max).powf(t) - 1.0
+            } else {
+                t * max
+            };
+            (format_value(v), ramp_color(t))
+        })
+        .collect();
+    let title = match (args.kernel > 0.0, args.log_scale) {
+        (false, false) => "Points per pixel",
+        (false, true) => "Points per pixel (log scale)",
+        (true, false) => "Point density",
+        (true, true) => "Point density (log scale)",
+    };
+
+    Ok(Legend {
+        title: title.to_string(),
+        entries,
+        continuous: true,
+    })
+}
+
+/// Separable Gaussian blur of a row-major grid, truncated at 3 sigma.
+fn gaussian_blur(grid: &mut [f64], width: usize, height: usize, sigma: f64) {
+    let radius = (3.0 * sigma).ceil() as isize;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|d| (-((d * d) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    let kernel: Vec<f64> = kernel.iter().map(|k| k / sum).collect();
+
+    let mut tmp = vec![0.0; grid.len()];
+    // Horizontal pass into tmp, vertical pass back into grid
+    for y in 0..height {
+        for x in 0..width {
+            tmp[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| {
+                    let sx = x as isize + k as isize - radius;
+                    if sx >= 0 && (sx as usize) < width {
+                        grid[y * width + sx as usize] * w
+                    } else {
+                        0.0
+                    }
+                })
+                .sum();
+        }
+    }
+    for y in 0..height {
+        for x in 0..width {
+            grid[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| {
+                    let sy = y as isize + k as isize - radius;
+                    if sy >= 0 && (sy as usize) < height {
+                        tmp[sy as usize * width + x] * w
+                    } else {
+                        0.0
+                    }
+                })
+                .sum();
+        }
+    }
+}
+
+/// Half size, in map units, of the view around an extent that is a single point.
+const POINT_EXTENT_PAD: f64 = 0.5;
+
+/// Give a zero-width or zero-height extent (a single point, a vertical or
+/// horizontal line) some room so it can be scaled onto an image: 1% of the
+/// other side, or a fixed margin when both sides are zero.
+fn pad_degenerate_extent(extent: gdal::vector::Envelope) -> gdal::vector::Envelope {
+    let (width, height) = (extent.MaxX - extent.MinX, extent.MaxY - extent.MinY);
+    let span = width.max(height);
+    let pad = if span > 0.0 {
+        span * 0.01
+    } else {
+        POINT_EXTENT_PAD
+    };
+    let mut padded = extent;
+    if width <= 0.0 {
+        padded.MinX -= pad;
+        padded.MaxX += pad;
+    }
+    if height <= 0.0 {
+        padded.MinY -= pad;
+        padded.MaxY += pad;
+    }
+    padded
+}
+
+fn union_envelope(
+    a: &gdal::vector::Envelope,
+    b: &gdal::vector::Envelope,
+) -> gdal::vector::Envelope {
+    gdal::vector::Envelope {
+        MinX: a.MinX.min(b.MinX),
+        MaxX: a.MaxX.max(b.MaxX),
+        MinY: a.MinY.min(b.MinY),
+        MaxY: a.MaxY.max(b.MaxY),
+    }
+}
+
+/// Apply --where (attribute filter) and --bbox (spatial filter) to a layer.
+fn apply_vector_filters(layer: &mut gdal::vector::Layer, args: &Args) -> Result<()> {
+    if let Some(query) = &args.where_clause {
+        layer
+            .set_attribute_filter(query)
+            .with_context(|| format!("Invalid --where expression: {}", query))?;
+    }
+
+    if let Some(bbox) = &args.bbox {
+        let [min_x, min_y, max_x, max_y] = bbox[..] else {
+            anyhow::bail!("--bbox needs 4 values: minx,miny,maxx,maxy");
+        };
+        if min_x >= max_x || min_y >= max_y {
+            anyhow::bail!("--bbox must be minx,miny,maxx,maxy with min < max");
+        }
+        layer.set_spatial_filter_rect(min_x, min_y, max_x, max_y);
+    }
+
+    Ok(())
+}
+
+/// What a single read of a layer's features tells the renderer.
+struct LayerScan {
+    /// Extent of the features passing the layer filters, clipped to --bbox
+    extent: Option<gdal::vector::Envelope>,
+    /// None when the layer was not read and the driver cannot count cheaply
+    feature_count: Option<u64>,
+    classifier: Option<FieldClassifier>,
+    sizer: Option<PointSizer>,
+}
+
+/// --color-by values collected while scanning a layer.
+enum FieldSample {
+    Numeric(Vec<f64>),
+    /// Number of features per distinct value
+    Categorical(std::collections::HashMap<String, usize>),
+}
+
+fn is_numeric_field(field_type: gdal::vector::OGRFieldType::Type) -> bool {
+    use gdal::vector::OGRFieldType;
+
+    matches!(
+        field_type,
+        OGRFieldType::OFTInteger | OGRFieldType::OFTInteger64 | OGRFieldType::OFTReal
+    )
+}
+
+/// Read the features passing the layer filters once, collecting the
+/// --color-by and --size-by statistics and, with `with_extent`, their
+/// extent (computed from the features, as drivers may ignore filters in
+/// GetExtent). Layers with nothing to collect are not read at all.
+fn scan_layer(
+    layer: &mut gdal::vector::Layer,
+    args: &Args,
+    with_extent: bool,
+) -> Result<LayerScan> {
+    let color_field = match &args.color_by {
+        Some(field) if has_field(layer, field) => Some((field, find_field(layer, field)?)),
+        _ => None,
+    };
+    let size_field = match &args.size_by {
+        Some(field) if has_field(layer, field) => {
+            let (field_idx, field_type) = find_field(layer, field)?;
+            if !is_numeric_field(field_type) {
+                anyhow::bail!("--size-by needs a numeric field, '{}' is not", field);
+            }
+            Some(field_idx)
+        }
+        _ => None,
+    };
+
+    if !with_extent && color_field.is_none() && size_field.is_none() {
+        return Ok(LayerScan {
+            extent: None,
+            feature_count: layer.try_feature_count(),
+            classifier: None,
+            sizer: None,
+        });
+    }
+
+    let mut sample = color_field.map(|(_, (_, field_type))| {
+        if is_numeric_field(field_type) {
+            FieldSample::Numeric(Vec::new())
+        } else {
+            FieldSample::Categorical(Default::default())
+        }
+    });
+    let mut extent: Option<gdal::vector::Envelope> = None;
+    let mut max_size_value = 0.0_f64;
+    let mut count = 0;
+    for feature in layer.features() {
+        count += 1;
+        if with_extent {
+            if let Some(geom) = feature.geometry() {
+                let env = geom.envelope();
+                extent = Some(match extent {
+                    Some(e) => union_envelope(&e, &env),
+                    None => env,
+                });
+            }
+        }
+        if let Some((_, (field_idx, _))) = color_field {
+            match &mut sample {
+                Some(FieldSample::Numeric(values)) => {
+                    if let Ok(Some(v)) = feature.field_as_double(field_idx) {
+                        if v.is_finite() {
+                            values.push(v);
+                        }
+                    }
+                }
+                Some(FieldSample::Categorical(counts)) => {
+                    if let Ok(Some(v)) = feature.field_as_string(field_idx) {
+                        *counts.entry(v).or_default() += 1;
+                    }
+                }
+                None => {}
+            }
+        }
+        if let Some(field_idx) = size_field {
+            if let Ok(Some(v)) = feature.field_as_double(field_idx) {
+                if v.is_finite() {
+                    max_size_value = max_size_value.max(v);
+                }
+            }
+        }
+    }
+
+    if let (Some(extent), Some(&[min_x, min_y, max_x, max_y])) =
+        (extent.as_mut(), args.bbox.as_deref())
+    {
+        extent.MinX = extent.MinX.max(min_x);
+        extent.MinY = extent.MinY.max(min_y);
+        extent.MaxX = extent.MaxX.min(max_x);
+        extent.MaxY = extent.MaxY.min(max_y);
+    }
+
+    let classifier = match (color_field, sample) {
+        (Some((field, (field_idx, _))), Some(sample)) => {
+            Some(build_classifier(field, field_idx, sample, args)?)
+        }
+        _ => None,
+    };
+    let sizer = size_field.map(|field_idx| PointSizer {
+        field_idx,
+        max_value: max_size_value,
+        min_radius: args.point_size as f64,
+        max_radius: args.max_point_size.max(args.point_size) as f64,
+    });
+
+    Ok(LayerScan {
+        extent,
+        feature_count: Some(count),
+        classifier,
+        sizer,
+    })
+}
+
+/// How vector features are drawn.
+#[derive(Debug, Clone, Copy)]
+struct VectorStyle {
+    line: Rgb<u8>,
+    /// Polygon fill color (None follows the line color)
+    fill_color: Option<Rgb<u8>>,
+    /// Polygon fill opacity (0-1); 0 draws outlines only
+    fill_opacity: f64,
+    line_width: f64,
+    antialias: bool,
+    symbol: PointSymbol,
+    /// Symbol radius in pixels
+    point_size: f64,
+    /// Point color (None follows the line color)
+    point_color: Option<Rgb<u8>>,
+}
+
+impl VectorStyle {
+    fn stroke(&self) -> Stroke {
+        Stroke {
+            color: self.line,
+            width: self.line_width,
+            antialias: self.antialias,
+        }
+    }
+}
+
+/// How lines and polygon outlines are drawn.
+#[derive(Debug, Clone, Copy)]
+struct Stroke {
+    color: Rgb<u8>,
+    width: f64,
+    antialias: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PointSymbol {
+    Square,
+    Circle,
+    Cross,
+    Triangle,
+}
+
+impl PointSymbol {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "square" => Ok(Self::Square),
+            "circle" => Ok(Self::Circle),
+            "cross" => Ok(Self::Cross),
+            "triangle" => Ok(Self::Triangle),
+            other => anyhow::bail!(
+                "Unknown symbol '{}'. Use: square, circle, cross or triangle",
+                other
+            ),
+        }
+    }
+}
+
+fn vector_style(args: &Args) -> Result<VectorStyle> {
+    let line = Rgb([100, 200, 255]); // Cyan-ish color for vectors
+
+    if !(0.0..=1.0).contains(&args.fill_opacity) {
+        anyhow::bail!(
+            "Fill opacity must be between 0 and 1, got {}",
+            args.fill_opacity
+        );
+    }
+    let fill_color = args
+        .fill_color
+        .as_deref()
+        .map(parse_hex_color)
+        .transpose()?;
+
+    if !args.line_width.is_finite() || args.line_width <= 0.0 {
+        anyhow::bail!("Line width must be positive, got {}", args.line_width);
+    }
+    let point_color = args
+        .point_color
+        .as_deref()
+        .map(parse_hex_color)
+        .transpose()?;
+
+    Ok(VectorStyle {
+        line,
+        fill_color,
+        fill_opacity: args.fill_opacity,
+        line_width: args.line_width,
+        antialias: args.antialias,
+        symbol: PointSymbol::from_name(&args.symbol)?,
+        point_size: args.point_size as f64,
+        point_color,
+    })
+}
+
+/// Title and color swatches printed under a styled render.
+struct Legend {
+    title: String,
+    entries: Vec<(String, Rgb<u8>)>,
+    /// Colors blend between evenly spaced entries (a colorbar) instead of
+    /// naming discrete classes
+    continuous: bool,
+}
+
+/// Width of a printed colorbar, in terminal cells.
+const COLORBAR_WIDTH: usize = 40;
+
+fn print_legends(legends: &[Legend], args: &Args) -> Result<()> {
+    if args.no_legend || legends.is_empty() {
+        return Ok(());
+    }
+    let depth = color_depth(args)?;
+    for legend in legends {
+        print_legend(legend, depth);
+    }
+    Ok(())
+}
+
+fn print_legend(legend: &Legend, depth: ColorDepth) {
+    println!("{}:", legend.title);
+    if legend.continuous && legend.entries.len() >= 2 {
+        print_colorbar(&legend.entries, depth);
+        return;
+    }
+    for (label, color) in &legend.entries {
+        println!(
+            "  {}  \x1b[0m {}",
+            color_escape(color.0, depth, true),
+            label
+        );
+    }
+}
+
+/// Gradient bar through the entry colors, with the entry labels as ticks
+/// underneath (labels that would collide are dropped).
+fn print_colorbar(entries: &[(String, Rgb<u8>)], depth: ColorDepth) {
+    let segments = (entries.len() - 1) as f64;
+    let bar: String = (0..COLORBAR_WIDTH)
+        .map(|i| {
+            let pos = i as f64 / (COLORBAR_WIDTH - 1) as f64 * segments;
+            let k = (pos as usize).min(entries.len() - 2);
+            let frac = pos - k as f64;
+            let (a, b) = (entries[k].1 .0, entries[k + 1].1 .0);
+            let color =
+                [0, 1, 2].map(|c| (a[c] as f64 + (b[c] as f64 - a[c] as f64) * frac).round() as u8);
+            format!("{} ", color_escape(color, depth, true))
+        })
+        .collect();
+    println!("  {}\x1b[0m", bar);
+
+    let mut ticks = vec![' '; COLORBAR_WIDTH];
+    let mut next_free = 0;
+    for (k, (label, _)) in entries.iter().enumerate() {
+        let len = label.chars().count();
+        let center = (k as f64 / segments * (COLORBAR_WIDTH - 1) as f64).round() as usize;
+        let start = if k == 0 {
+            0
+        } else if k == entries.len() - 1 {
+            COLORBAR_WIDTH.saturating_sub(len)
+        } else {
+            center.saturating_sub(len / 2)
+        };
+        if start < next_free || start + len > COLORBAR_WIDTH {
+            continue;
+        }
+        for (i, c) in label.chars().enumerate() {
+            ticks[start + i] = c;
+        }
+        next_free = start + len + 1;
+    }
+    println!("  {}", ticks.into_iter().collect::<String>().trim_end());
+}
+
+/// Maps a feature attribute to a color for --color-by.
+struct FieldClassifier {
+    field_idx: usize,
+    kind: ClassifierKind,
+    legend: Legend,
+}
+
+enum ClassifierKind {
+    /// One color per distinct string value
+    Categorical(std::collections::HashMap<String, Rgb<u8>>),
+    /// Upper class breaks (exclusive) and one color per class
+    Graduated {
+        breaks: Vec<f64>,
+        colors: Vec<Rgb<u8>>,
+    },
+}
+
+// Qualitative palette for categorical fields, readable on a dark background
+const CATEGORY_PALETTE: [[u8; 3]; 10] = [
+    [102, 194, 165],
+    [252, 141, 98],
+    [141, 160, 203],
+    [231, 138, 195],
+    [166, 216, 84],
+    [255, 217, 47],
+    [229, 196, 148],
+    [128, 177, 211],
+    [251, 128, 114],
+    [188, 128, 189],
+];
+
+// Features with a null attribute, or categories beyond the palette
+const OTHER_COLOR: Rgb<u8> = Rgb([110, 110, 110]);
+
+impl FieldClassifier {
+    fn color_for(&self, feature: &gdal::vector::Feature) -> Rgb<u8> {
+        match &self.kind {
+            ClassifierKind::Categorical(colors) => feature
+                .field_as_string(self.field_idx)
+                .ok()
+                .flatten()
+                .and_then(|v| colors.get(&v).copied())
+                .unwrap_or(OTHER_COLOR),
+            ClassifierKind::Graduated { breaks, colors } => {
+                match feature.field_as_double(self.field_idx).ok().flatten() {
+                    Some(v) if v.is_finite() => colors[breaks.partition_point(|&b| b <= v)],
+                    _ => OTHER_COLOR,
+                }
+            }
+        }
+    }
+}
+
+/// Index and type of a layer field, or an error listing the available fields.
+fn find_field(
+    layer: &gdal::vector::Layer,
+    field: &str,
+) -> Result<(usize, gdal::vector::OGRFieldType::Type)> {
+    let defn = layer.defn();
+    let found = defn
+        .fields()
+        .enumerate()
+        .find(|(_, f)| f.name() == field)
+        .map(|(i, f)| (i, f.field_type()));
+    match found {
+        Some(found) => Ok(found),
+        None => {
+            let fields: Vec<String> = defn.fields().map(|f| f.name()).collect();
+            anyhow::bail!(
+                "Field '{}' not found. Available fields: {}",
+                field,
+                fields.join(", ")
+            );
+        }
+    }
+}
+
+fn has_field(layer: &gdal::vector::Layer, field: &str) -> bool {
+    layer.defn().fields().any(|f| f.name() == field)
+}
+
+/// --color-by, --size-by and --label apply to the layers that have the
+/// field; fail only when none of them does.
+fn check_field_options(layers: &[&gdal::vector::Layer], args: &Args) -> Result<()> {
+    let options = [
+        ("--color-by", &args.color_by),
+        ("--size-by", &args.size_by),
+        ("--label", &args.label),
+    ];
+    for (option, field) in options {
+        let (Some(field), Some(first)) = (field, layers.first()) else {
+            continue;
+        };
+        let missing: Vec<&&gdal::vector::Layer> =
+            layers.iter().filter(|l| !has_field(l, field)).collect();
+        if missing.len() == layers.len() {
+            find_field(first, field)?;
+        }
+        for layer in missing {
+            eprintln!(
+                "Warning: layer '{}' has no field '{}', {} is not applied to it",
+                layer.name(),
+                field,
+                option
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Maps a numeric attribute to a symbol radius for --size-by, so symbol
+/// area is proportional to the value.
+struct PointSizer {
+    field_idx: usize,
+    max_value: f64,
+    min_radius: f64,
+    max_radius: f64,
+}
+
+impl PointSizer {
+    fn radius_for(&self, feature: &gdal::vector::Feature) -> f64 {
+        match feature.field_as_double(self.field_idx).ok().flatten() {
+            Some(v) if v.is_finite() && v > 0.0 && self.max_value > 0.0 => {
+                (self.max_radius * (v / self.max_value).sqrt()).max(self.min_radius)
+            }
+            _ => self.min_radius,
+        }
+    }
+}
+
+/// Build a categorical palette (string fields) or a graduated ramp
+/// (numeric fields) from the values of `field` collected by `scan_layer`.
+fn build_classifier(
+    field: &str,
+    field_idx: usize,
+    sample: FieldSample,
+    args: &Args,
+) -> Result<FieldClassifier> {
+    match sample {
+        FieldSample::Numeric(mut values) => {
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+            let n_classes = args.classes.clamp(1, 12);
+            let breaks = match args.classify.as_str() {
+                "quantile" => quantile_breaks(&values, n_classes),
+                "equal" => equal_interval_breaks(&values, n_classes),
+                other => {
+                    anyhow::bail!("Unknown classification '{}'. Use: quantile or equal", other)
+                }
+            };
+            let colors: Vec<Rgb<u8>> = (0..=breaks.len())
+                .map(|i| ramp_color(i as f64 / breaks.len().max(1) as f64))
+                .collect();
+
+            let min = values.first().copied().unwrap_or(0.0);
+            let max = values.last().copied().unwrap_or(0.0);
+            let bounds: Vec<f64> = std::iter::once(min)
+                .chain(breaks.iter().copied())
+                .chain(std::iter::once(max))
+                .collect();
+            let entries = bounds
+                .windows(2)
+                .zip(&colors)
+                .map(|(w, &c)| {
+                    (
+                        format!("{} - {}", format_value(w[0]), format_value(w[1])),
+                        c,
+                    )
+                })
+                .collect();
+
+            Ok(FieldClassifier {
+                field_idx,
+                kind: ClassifierKind::Graduated { breaks, colors },
+                legend: Legend {
+                    title: format!("{} ({})", field, args.classify),
+                    entries,
+                    continuous: false,
+                },
+            })
+        }
+        FieldSample::Categorical(counts) => {
+            // Most frequent categories get palette colors, the rest are "Other"
+            let mut categories: Vec<(String, usize)> = counts.into_iter().collect();
+            categories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            let mut colors = std::collections::HashMap::new();
+            let mut entries = Vec::new();
+            for ((name, _), rgb) in categories.iter().zip(CATEGORY_PALETTE) {
+                colors.insert(name.clone(), Rgb(rgb));
+                entries.push((name.clone(), Rgb(rgb)));
+            }
+            if categories.len() > CATEGORY_PALETTE.len() {
+                entries.push((
+                    format!(
+                        "Other ({} values)",
+                        categories.len() - CATEGORY_PALETTE.len()
+                    ),
+                    OTHER_COLOR,
+                ));
+            }
+
+            Ok(FieldClassifier {
+                field_idx,
+                kind: ClassifierKind::Categorical(colors),
+                legend: Legend {
+                    title: field.to_string(),
+                    entries,
+                    continuous: false,
+                },
+            })
+        }
+    }
+}
+
+/// Class breaks putting the same number of sorted values in each class.
+fn quantile_breaks(sorted: &[f64], n_classes: usize) -> Vec<f64> {
+    if sorted.is_empty() {
+        return Vec::new();
+    }
+    let mut breaks: Vec<f64> = (1..n_classes)
+        .map(|i| sorted[(sorted.len() * i / n_classes).min(sorted.len() - 1)])
+        .collect();
+    // Heavily repeated values produce duplicate breaks
+    breaks.dedup();
+    breaks
+}
+
+/// Class breaks splitting the value range into equal-width intervals.
+fn equal_interval_breaks(sorted: &[f64], n_classes: usize) -> Vec<f64> {
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+        return Vec::new();
+    };
+    if max <= min {
+        return Vec::new();
+    }
+    (1..n_classes)
+        .map(|i| min + (max - min) * i as f64 / n_classes as f64)
+        .collect()
+}
+
+// Viridis control points for sequential ramps
+const RAMP_STOPS: [[u8; 3]; 5] = [
+    [68, 1, 84],
+    [59, 82, 139],
+    [33, 145, 140],
+    [94, 201, 98],
+    [253, 231, 37],
+];
+
+/// Sequential color ramp, `t` from 0 (low) to 1 (high).
+fn ramp_color(t: f64) -> Rgb<u8> {
+    let pos = t.clamp(0.0, 1.0) * (RAMP_STOPS.len() - 1) as f64;
+    let i = (pos as usize).min(RAMP_STOPS.len() - 2);
+    let frac = pos - i as f64;
+    let (a, b) = (RAMP_STOPS[i], RAMP_STOPS[i + 1]);
+    Rgb([0, 1, 2].map(|c| (a[c] as f64 + (b[c] as f64 - a[c] as f64) * frac).round() as u8))
+}
+
+/// Compact number formatting for legend labels.
+fn format_value(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e15 {
+        format!("{}", v as i64)
+    } else if v.abs() >= 1000.0 {
+        format!("{:.0}", v)
+    } else {
+        format!("{:.2}", v)
+    }
+}
+
+/// Parse a `#rrggbb` (or `rrggbb`) color.
+fn parse_hex_color(s: &str) -> Result<Rgb<u8>> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    // Checked up front: from_str_radix alone would also take a sign
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid color '{}'. Use #rrggbb, e.g. #ff8800", s);
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    Ok(Rgb([channel(0), channel(2), channel(4)]))
+}
+
+/// Affine mapping from map coordinates to image pixel coordinates.
+#[derive(Debug, Clone, Copy)]
+struct PixelTransform {
+    coeffs: [f64; 6],
+}
+
+impl PixelTransform {
+    /// North-up mapping of `extent` onto a `width` x `height` image.
+    fn from_extent(extent: &gdal::vector::Envelope, width: usize, height: usize) -> Self {
+        let scale_x = width as f64 / (extent.MaxX - extent.MinX);
+        let scale_y = height as f64 / (extent.MaxY - extent.MinY);
+        Self {
+            coeffs: [
+                -extent.MinX * scale_x,
+                scale_x,
+                0.0,
+                extent.MaxY * scale_y,
+                0.0,
+                -scale_y,
+            ],
+        }
+    }
+
+    /// Mapping onto a raster rendered at `out_size` from a source of
+    /// `src_size` pixels, by inverting the raster's geotransform.
+    fn from_geo_transform(
+        gt: &[f64; 6],
+        src_size: (usize, usize),
+        out_size: (usize, usize),
+    ) -> Result<Self> {
+        let det = gt[1] * gt[5] - gt[2] * gt[4];
+        if det == 0.0 {
+            anyhow::bail!("Raster geotransform is not invertible");
+        }
+        let sx = out_size.0 as f64 / src_size.0 as f64;
+        let sy = out_size.1 as f64 / src_size.1 as f64;
+
+        // Inverse of x = gt0 + col*gt1 + row*gt2, y = gt3 + col*gt4 + row*gt5
+        let (a, b) = (gt[5] / det, -gt[2] / det);
+        let (d, e) = (-gt[4] / det, gt[1] / det);
+        Ok(Self {
+            coeffs: [
+                -(a * gt[0] + b * gt[3]) * sx,
+                a * sx,
+                b * sx,
+                -(d * gt[0] + e * gt[3]) * sy,
+                d * sy,
+                e * sy,
+            ],
+        })
+    }
+
+    /// Inverse of `to_pixel`.
+    fn to_map(self, px: f64, py: f64) -> (f64, f64) {
+        let c = &self.coeffs;
+        let det = c[1] * c[5] - c[2] * c[4];
+        let (dx, dy) = (px - c[0], py - c[3]);
+        ((c[5] * dx - c[2] * dy) / det, (c[1] * dy - c[4] * dx) / det)
+    }
+
+    /// Size of an output pixel in map units (geometric mean of both axes).
+    fn map_units_per_pixel(self) -> f64 {
+        let c = &self.coeffs;
+        1.0 / (c[1] * c[5] - c[2] * c[4]).abs().sqrt()
+    }
+
+    fn to_pixel(self, x: f64, y: f64) -> (f64, f64) {
+        let c = &self.coeffs;
+        (c[0] + c[1] * x + c[2] * y, c[3] + c[4] * x + c[5] * y)
+    }
+}
+
+/// Build a transform from `from` to `to`, or None when both CRSs are the same
+/// (or one of them is unknown).
+fn reprojection(
+    from: Option<gdal::spatial_ref::SpatialRef>,
+    to: Option<gdal::spatial_ref::SpatialRef>,
+) -> Result<Option<gdal::spatial_ref::CoordTransform>> {
+    let (Some(mut from), Some(mut to)) = (from, to) else {
+        return Ok(None);
+    };
+    if from == to {
+        return Ok(None);
+    }
+    // Keep x = easting/longitude regardless of the CRS axis order
+    from.set_axis_mapping_strategy(gdal::spatial_ref::AxisMappingStrategy::TraditionalGisOrder);
+    to.set_axis_mapping_strategy(gdal::spatial_ref::AxisMappingStrategy::TraditionalGisOrder);
+    let transform = gdal::spatial_ref::CoordTransform::new(&from, &to)
+        .context("Cannot create coordinate transformation")?;
+    Ok(Some(transform))
+}
+
+/// Draw every feature of a layer, reprojecting geometries first if needed.
+/// Returns the --color-by legend, if any.
+fn draw_layer(
+    img: &mut RgbaImage,
+    layer: &mut gdal::vector::Layer,
+    transform: &PixelTransform,
+    style: VectorStyle,
+    reproject: Option<&gdal::spatial_ref::CoordTransform>,
+    scan: LayerScan,
+    args: &Args,
+) -> Result<(Option<Legend>, Vec<Label>)> {
+    let LayerScan {
+        feature_count,
+        classifier,
+        sizer,
+        ..
+    } = scan;
+    let label_field = match &args.label {
+        Some(field) if has_field(layer, field) => Some(find_field(layer, field)?.0),
+        _ => None,
+    };
+    let mut labels = Vec::new();
+
+    // Detail below this size (in map units) cannot show up in the output
+    let pixel_size = transform.map_units_per_pixel();
+    let tolerance = args.simplify.max(0.0) * pixel_size;
+    let mut progress = Progress::new(&layer.name(), feature_count.unwrap_or(0));
+
+    // The iterator resets the layer's reading position
+    let mut skipped = std::collections::BTreeMap::new();
+    for (n, feature) in layer.features().enumerate() {
+        progress.update(n as u64 + 1);
+        let Some(mut geom) = feature.geometry() else {
+            continue;
+        };
+
+        let reprojected;
+        if let Some(ct) = reproject {
+            // Skip features that fall outside the target CRS's domain
+            let Ok(transformed) = geom.transform(ct) else {
+                continue;
+            };
+            reprojected = transformed;
+            geom = &reprojected;
+        }
+
+        let radius = sizer
+            .as_ref()
+            .map_or(style.point_size, |s| s.radius_for(&feature));
+        // Anchored on the full geometry, before small features are dropped
+        if let Some(field_idx) = label_field {
+            labels.extend(feature_label(&feature, field_idx, geom, transform, radius)?);
+        }
+
+        let simplified;
+        if !is_point_geometry(geom) {
+            let env = geom.envelope();
+            if env.MaxX - env.MinX < pixel_size && env.MaxY - env.MinY < pixel_size {
+                continue; // Sub-pixel feature
+            }
+            // Without GEOS simplification fails; draw the full geometry then
+            if tolerance > 0.0 {
+                if let Ok(g) = geom.simplify_preserve_topology(tolerance) {
+                    simplified = g;
+                    geom = &simplified;
+                }
+            }
+        }
+
+        let mut feature_style = match &classifier {
+            Some(classifier) => VectorStyle {
+                line: classifier.color_for(&feature),
+                point_color: None,
+                ..style
+            },
+            None => style,
+        };
+        feature_style.point_size = radius;
+        draw_geometry(img, geom, transform, &feature_style, &mut skipped);
+    }
+    progress.finish();
+
+    if !skipped.is_empty() {
+        let counts: Vec<String> = skipped
+            .iter()
+            .map(|(name, count)| format!("{} ({})", name, count))
+            .collect();
+        eprintln!(
+            "Skipped unsupported geometries in {}: {}",
+            layer.name(),
+            counts.join(", ")
+        );
+    }
+
+    Ok((classifier.map(|c| c.legend), labels))
+}
+
+fn is_point_geometry(geom: &gdal::vector::Geometry) -> bool {
+    use gdal::vector::OGRwkbGeometryType;
+
+    matches!(
+        gdal::vector::geometry_type_flatten(geom.geometry_type()),
+        OGRwkbGeometryType::wkbPoint | OGRwkbGeometryType::wkbMultiPoint
+    )
+}
+
+/// Layers with fewer features render too fast to need a progress indicator.
+const PROGRESS_MIN_FEATURES: u64 = 10_000;
+
+/// Percentage indicator on stderr for long renders, shown only on a terminal.
+struct Progress {
+    label: String,
+    total: u64,
+    last_percent: Option<u64>,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(label: &str, total: u64) -> Self {
+        use std::io::IsTerminal;
+
+        Self {
+            label: label.to_string(),
+            total,
+            last_percent: None,
+            enabled: total >= PROGRESS_MIN_FEATURES && std::io::stderr().is_terminal(),
+        }
+    }
+
+    fn update(&mut self, done: u64) {
+        if !self.enabled {
+            return;
+        }
+        let percent = (done * 100 / self.total).min(100);
+        if self.last_percent != Some(percent) {
+            self.last_percent = Some(percent);
+            eprint!("\r{}: {}% ({}/{})", self.label, percent, done, self.total);
+        }
+    }
+
+    fn finish(&self) {
+        if self.enabled {
+            // Clear the indicator line
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+const GRATICULE_COLOR: Rgb<u8> = Rgb([160, 160, 160]);
+/// Meters per degree of latitude (and of longitude at the equator).
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// --decorations for a rendered raster, placed with its geotransform.
+fn draw_raster_decorations(img: DynamicImage, dataset: &Dataset) -> DynamicImage {
+    let Ok(gt) = dataset.geo_transform() else {
+        eprintln!("Raster has no geotransform, skipping --decorations");
+        return img;
+    };
+    let mut canvas = img.to_rgba8();
+    let Ok(transform) = PixelTransform::from_geo_transform(
+        &gt,
+        dataset.raster_size(),
+        (canvas.width() as usize, canvas.height() as usize),
+    ) else {
+        return img;
+    };
+    draw_decorations(&mut canvas, &transform, dataset.spatial_ref().ok().as_ref());
+    DynamicImage::ImageRgba8(canvas)
+}
+
+/// Graticule with edge labels, scale bar (bottom left) and north arrow (top
+/// right). The scale bar and arrow need a known CRS.
+fn draw_decorations(
+    img: &mut RgbaImage,
+    transform: &PixelTransform,
+    srs: Option<&gdal::spatial_ref::SpatialRef>,
+) {
+    draw_graticule(img, transform, srs.is_some_and(|s| s.is_geographic()));
+    if let Some(srs) = srs {
+        draw_scale_bar(img, transform, srs);
+    }
+    draw_north_arrow(img, transform, srs);
+}
+
+/// Largest 1, 2 or 5 times a power of ten that is not above `v`.
+fn nice_floor(v: f64) -> f64 {
+    let magnitude = 10f64.powf(v.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&n| n <= v)
+        .unwrap_or(magnitude)
+}
+
+/// Grid lines at round coordinates in the map CRS (degrees for geographic
+/// data), labelled along the top and left edges.
+fn draw_graticule(img: &mut RgbaImage, transform: &PixelTransform, geographic: bool) {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)].map(|(px, py)| transform.to_map(px, py));
+    let (min_x, max_x, min_y, max_y) = corners.iter().fold(
+        (
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(x0, x1, y0, y1), &(x, y)| (x0.min(x), x1.max(x), y0.min(y), y1.max(y)),
+    );
+    let step_x = nice_floor((max_x - min_x) / 4.0);
+    let step_y = nice_floor((max_y - min_y) / 4.0);
+    if !(step_x.is_finite() && step_y.is_finite() && step_x > 0.0 && step_y > 0.0) {
+        return;
+    }
+
+    let label = |v: f64, pos: &str, neg: &str| -> String {
+        if !geographic {
+            format_value(v)
+        } else if v > 0.0 {
+            format!("{}{}", format_value(v), pos)
+        } else if v < 0.0 {
+            format!("{}{}", format_value(-v), neg)
+        } else {
+            "0".to_string()
+        }
+    };
+
+    let mut placed: Vec<[i32; 4]> = Vec::new();
+    let mut place_label = |img: &mut RgbaImage, x: i32, y: i32, text: &str| {
+        let (tw, th) = text_size(text);
+        let rect = [x - 2, y - 2, x + tw + 2, y + th + 2];
+        let inside =
+            x >= 1 && y >= 1 && x + tw < img.width() as i32 && y + th < img.height() as i32;
+        if inside && !placed.iter().any(|r| rects_overlap(r, &rect)) {
+            draw_text(img, x, y, text);
+            placed.push(rect);
+        }
+    };
+
+    // Meridians / vertical grid lines
+    let mut lines = Vec::new();
+    for k in (min_x / step_x).ceil() as i64..=(max_x / step_x).floor() as i64 {
+        let x = k as f64 * step_x;
+        let (top, bottom) = (transform.to_pixel(x, max_y), transform.to_pixel(x, min_y));
+        draw_line_antialiased(img, top, bottom, GRATICULE_COLOR);
+        lines.push((top.0, label(x, "E", "W"), true));
+    }
+    // Parallels / horizontal grid lines
+    for k in (min_y / step_y).ceil() as i64..=(max_y / step_y).floor() as i64 {
+        let y = k as f64 * step_y;
+        let (left, right) = (transform.to_pixel(min_x, y), transform.to_pixel(max_x, y));
+        draw_line_antialiased(img, left, right, GRATICULE_COLOR);
+        lines.push((left.1, label(y, "N", "S"), false));
+    }
+
+    // Labels after all lines so no line crosses a label
+    for (pos, text, vertical) in lines {
+        let (tw, th) = text_size(&text);
+        if vertical {
+            place_label(img, pos.round() as i32 - tw / 2, 2, &text);
+        } else {
+            place_label(img, 2, pos.round() as i32 - th / 2, &text);
+        }
+    }
+}
+
+/// Scale bar about a quarter of the image wide, measured at the center.
+fn draw_scale_bar(
+    img: &mut RgbaImage,
+    transform: &PixelTransform,
+    srs: &gdal::spatial_ref::SpatialRef,
+) {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let (x0, y0) = transform.to_map(w / 2.0, h / 2.0);
+    let (x1, y1) = transform.to_map(w / 2.0 + 1.0, h / 2.0);
+    let meters_per_pixel = if srs.is_geographic() {
+        ((x1 - x0) * y0.to_radians().cos()).hypot(y1 - y0) * METERS_PER_DEGREE
+    } else {
+        (x1 - x0).hypot(y1 - y0) * srs.linear_units()
+    };
+    if !(meters_per_pixel.is_finite() && meters_per_pixel > 0.0) {
+        return;
+    }
+
+    let length = nice_floor(w / 4.0 * meters_per_pixel);
+    let bar_px = (length / meters_per_pixel).round() as i32;
+    let text = if length >= 1000.0 {
+        format!("{} km", format_value(length / 1000.0))
+    } else {
+        format!("{} m", format_value(length))
+    };
+
+    let (left, bottom) = (8, h as i32 - 8);
+    if bar_px < 2 || bottom < 20 || left + bar_px + 1 >= w as i32 {
+        return;
+    }
+    // White bar with a dark outline, label above
+    for y in bottom - 4..=bottom {
+        for x in left - 1..=left + bar_px + 1 {
+            let edge = y == bottom - 4 || y == bottom || x < left || x > left + bar_px;
+            let color = if edge { LABEL_HALO } else { LABEL_COLOR };
+            img.put_pixel(x as u32, y as u32, color.to_rgba());
+        }
+    }
+    draw_text(img, left, bottom - 6 - GLYPH_HEIGHT, &text);
+}
+
+/// Arrow in the top-right corner pointing to true north, which is not
+/// straight up in many projections (polar ones in particular).
+fn draw_north_arrow(
+    img: &mut RgbaImage,
+    transform: &PixelTransform,
+    srs: Option<&gdal::spatial_ref::SpatialRef>,
+) {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let center = (w - 16.0, 32.0);
+    if w < 48.0 || h < 64.0 {
+        return;
+    }
+
+    let (dx, dy) = srs
+        .and_then(|srs| north_direction(transform, srs, center))
+        .unwrap_or((0.0, -1.0));
+    let (perp_x, perp_y) = (-dy, dx);
+    let at = |along: f64, across: f64| {
+        (
+            center.0 + dx * along + perp_x * across,
+            center.1 + dy * along + perp_y * across,
+        )
+    };
+
+    let outline = [at(10.0, 0.0), at(-7.0, 6.0), at(-3.0, 0.0), at(-7.0, -6.0)];
+    fill_polygon(img, &[outline.to_vec()], LABEL_COLOR, 1.0);
+    for (i, &p) in outline.iter().enumerate() {
+        draw_line_antialiased(img, p, outline[(i + 1) % outline.len()], LABEL_HALO);
+    }
+
+    let (nx, ny) = at(19.0, 0.0);
+    draw_text(
+        img,
+        nx.round() as i32 - GLYPH_WIDTH / 2,
+        ny.round() as i32 - GLYPH_HEIGHT / 2,
+        "N",
+    );
+}
+
+/// Unit vector (in pixels) pointing north at `at`, found by stepping north in
+/// geographic coordinates and projecting back.
+fn north_direction(
+    transform: &PixelTransform,
+    srs: &gdal::spatial_ref::SpatialRef,
+    at: (f64, f64),
+) -> Option<(f64, f64)> {
+    let geographic = srs.geog_cs().ok()?;
+    let to_geo = reprojection(Some(srs.clone()), Some(geographic.clone())).ok()?;
+    let from_geo = reprojection(Some(geographic), Some(srs.clone())).ok()?;
+    let (Some(to_geo), Some(from_geo)) = (to_geo, from_geo) else {
+        // Already geographic: north is +y
+        let (x, y) = transform.to_map(at.0, at.1);
+        let north = transform.to_pixel(x, y + 1.0);
+        return normalize((north.0 - at.0, north.1 - at.1));
+    };
+
+    let (x, y) = transform.to_map(at.0, at.1);
+    let (mut xs, mut ys, mut zs) = ([x], [y], [0.0]);
+    to_geo.transform_coords(&mut xs, &mut ys, &mut zs).ok()?;
+    // Step towards the pole, or away from it when already there
+    let step = if ys[0] < 89.9 { 0.01 } else { -0.01 };
+    ys[0] += step;
+    from_geo.transform_coords(&mut xs, &mut ys, &mut zs).ok()?;
+    let north = transform.to_pixel(xs[0], ys[0]);
+    let (dx, dy) = (north.0 - at.0, north.1 - at.1);
+    normalize(if step > 0.0 { (dx, dy) } else { (-dx, -dy) })
+}
+
+fn normalize((x, y): (f64, f64)) -> Option<(f64, f64)> {
+    let len = x.hypot(y);
+    (len.is_finite() && len > 0.0).then(|| (x / len, y / len))
+}
+
+fn rects_overlap(a: &[i32; 4], b: &[i32; 4]) -> bool {
+    a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+/// Longest label text, in characters.
+const MAX_LABEL_CHARS: usize = 24;
+const GLYPH_WIDTH: i32 = 5;
+const GLYPH_HEIGHT: i32 = 8;
+const LABEL_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const LABEL_HALO: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// A --label candidate collected while drawing a layer.
+struct Label {
+    text: String,
+    /// Pixel position from `label_anchor`
+    anchor: (f64, f64),
+    is_point: bool,
+    /// Symbol radius a point label has to clear
+    radius: f64,
+}
+
+/// Label for a feature's (already reprojected) geometry, if the field has a
+/// value and the geometry somewhere to put it.
+fn feature_label(
+    feature: &gdal::vector::Feature,
+    field_idx: usize,
+    geom: &gdal::vector::Geometry,
+    transform: &PixelTransform,
+    radius: f64,
+) -> Result<Option<Label>> {
+    let Some(text) = feature.field(field_idx)?.map(|v| format_field_value(&v)) else {
+        return Ok(None);
+    };
+    let text: String = text.trim().chars().take(MAX_LABEL_CHARS).collect();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    Ok(
+        label_anchor(geom, transform).map(|(anchor, is_point)| Label {
+            text,
+            anchor,
+            is_point,
+            radius,
+        }),
+    )
+}
+
+/// Draw labels in order, skipping those that would overlap one already
+/// placed.
+fn place_labels(img: &mut RgbaImage, labels: &[Label]) {
+    let mut placed: Vec<[i32; 4]> = Vec::new();
+    for label in labels {
+        let (w, h) = text_size(&label.text);
+        let (x, y) = (label.anchor.0.round() as i32, label.anchor.1.round() as i32);
+        // Points are labelled beside the symbol, lines and areas centered
+        let gap = if label.is_point {
+            label.radius.round() as i32 + 3
+        } else {
+            2
+        };
+        let centered = (x - w / 2, y - h / 2);
+        let right = (x + gap, y - h / 2);
+        let left = (x - gap - w, y - h / 2);
+        let above = (x - w / 2, y - gap - h);
+        let below = (x - w / 2, y + gap);
+        let candidates = if label.is_point {
+            [right, left, above, below, centered]
+        } else {
+            [centered, above, below, right, left]
+        };
+
+        // Rectangles include the halo and a pixel of padding
+        let fits = |&(lx, ly): &(i32, i32)| {
+            let rect = [lx - 2, ly - 2, lx + w + 2, ly + h + 2];
+            let inside =
+                lx >= 1 && ly >= 1 && lx + w < img.width() as i32 && ly + h < img.height() as i32;
+            inside && !placed.iter().any(|r| rects_overlap(r, &rect))
+        };
+        if let Some(&(lx, ly)) = candidates.iter().find(|c| fits(c)) {
+            draw_text(img, lx, ly, &label.text);
+            placed.push([lx - 2, ly - 2, lx + w + 2, ly + h + 2]);
+        }
+    }
+}
+
+/// Pixel position to label a geometry at, and whether it is a point: the
+/// point itself, the midpoint along a line, or an interior point of a
+/// polygon. Multi-part geometries use their largest part.
+fn label_anchor(
+    geom: &gdal::vector::Geometry,
+    transform: &PixelTransform,
+) -> Option<((f64, f64), bool)> {
+    use gdal::vector::OGRwkbGeometryType;
+
+    match gdal::vector::geometry_type_flatten(geom.geometry_type()) {
+        OGRwkbGeometryType::wkbPoint => {
+            if geom.point_count() == 0 {
+                return None; // POINT EMPTY
+            }
+            let (x, y, _) = geom.get_point(0);
+            Some((transform.to_pixel(x, y), true))
+        }
+        OGRwkbGeometryType::wkbLineString => {
+            let mut points = Vec::new();
+            geom.get_points(&mut points);
+            let pixels: Vec<(f64, f64)> = points
+                .iter()
+                .map(|&(x, y, _)| transform.to_pixel(x, y))
+                .collect();
+            line_midpoint(&pixels).map(|p| (p, false))
+        }
+        OGRwkbGeometryType::wkbPolygon | OGRwkbGeometryType::wkbTriangle => {
+            let mut points = Vec::new();
+            let rings: Vec<Vec<(f64, f64)>> = (0..geom.geometry_count())
+                .map(|i| {
+                    points.clear();
+                    geom.get_geometry(i).get_points(&mut points);
+                    points
+                        .iter()
+                        .map(|&(x, y, _)| transform.to_pixel(x, y))
+                        .collect()
+                })
+                .collect();
+            interior_point(&rings).map(|p| (p, false))
+        }
+        _ => {
+            let size = |g: &gdal::vector::Geometry| g.area().max(g.length());
+            let largest = (0..geom.geometry_count())
+                .map(|i| geom.get_geometry(i))
+                .max_by(|a, b| {
+                    size(a)
+                        .partial_cmp(&size(b))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })?;
+            label_anchor(&largest, transform)
+        }
+    }
+}
+
+/// Point halfway along a polyline.
+fn line_midpoint(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let seg_len = |(a, b): (&(f64, f64), &(f64, f64))| (b.0 - a.0).hypot(b.1 - a.1);
+    let total: f64 = points.iter().zip(points.iter().skip(1)).map(seg_len).sum();
+    let mut remaining = total / 2.0;
+    for (a, b) in points.iter().zip(points.iter().skip(1)) {
+        let len = seg_len((a, b));
+        if len > 0.0 && remaining <= len {
+            let t = remaining / len;
+            return Some((a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1)));
+        }
+        remaining -= len;
+    }
+    points.first().copied()
+}
+
+/// A point inside a polygon (holes excluded): the middle of the widest span
+/// on the scanline through the middle of the exterior ring.
+fn interior_point(rings: &[Vec<(f64, f64)>]) -> Option<(f64, f64)> {
+    let exterior = rings.first()?;
+    let (min_y, max_y) = exterior
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &(_, y)| {
+            (lo.min(y), hi.max(y))
+        });
+    if !min_y.is_finite() {
+        return None;
+    }
+    let scan_y = (min_y + max_y) / 2.0;
+
+    let mut crossings = Vec::new();
+    for ring in rings {
+        if ring.len() < 2 {
+            continue;
+        }
+        // Rings are normally closed, but don't rely on it
+        let closing = (ring[ring.len() - 1], ring[0]);
+        for (&(x0, y0), &(x1, y1)) in ring
+            .iter()
+            .zip(ring.iter().skip(1))
+            .chain(std::iter::once((&closing.0, &closing.1)))
+        {
+            if (y0 <= scan_y) != (y1 <= scan_y) {
+                crossings.push(x0 + (scan_y - y0) / (y1 - y0) * (x1 - x0));
             }
         }
     }
+    crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    crossings
+        .chunks_exact(2)
+        .max_by(|a, b| {
+            (a[1] - a[0])
+                .partial_cmp(&(b[1] - b[0]))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|span| ((span[0] + span[1]) / 2.0, scan_y))
 }
 
-fn draw_point(img: &mut RgbImage, x: i32, y: i32, color: Rgb<u8>) {
-    // Draw a small cross for points
-    for dx in -1..=1 {
+fn text_size(text: &str) -> (i32, i32) {
+    let chars = text.chars().count() as i32;
+    (chars * (GLYPH_WIDTH + 1) - 1, GLYPH_HEIGHT)
+}
+
+/// Draw text with its top-left corner at (x, y), outlined with a one pixel
+/// halo so it reads on any background. Characters outside printable ASCII
+/// are drawn as '?'.
+fn draw_text(img: &mut RgbaImage, x: i32, y: i32, text: &str) {
+    let lit_pixels: Vec<(i32, i32)> = text
+        .chars()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            let code = c as usize;
+            let glyph = if (0x20..0x7f).contains(&code) {
+                FONT_5X8[code - 0x20]
+            } else {
+                FONT_5X8[b'?' as usize - 0x20]
+            };
+            let left = x + i as i32 * (GLYPH_WIDTH + 1);
+            (0..GLYPH_WIDTH).flat_map(move |col| {
+                (0..GLYPH_HEIGHT)
+                    .filter(move |row| glyph[col as usize] >> row & 1 == 1)
+                    .map(move |row| (left + col, y + row))
+            })
+        })
+        .collect();
+
+    let mut put = |px: i32, py: i32, color: Rgb<u8>| {
+        if px >= 0 && py >= 0 && (px as u32) < img.width() && (py as u32) < img.height() {
+            img.put_pixel(px as u32, py as u32, color.to_rgba());
+        }
+    };
+    for &(px, py) in &lit_pixels {
         for dy in -1..=1 {
-            let px = x + dx;
-            let py = y + dy;
-            if px >= 0 && py >= 0 && (px as u32) < img.width() && (py as u32) < img.height() {
-                img.put_pixel(px as u32, py as u32, color);
+            for dx in -1..=1 {
+                put(px + dx, py + dy, LABEL_HALO);
             }
         }
     }
+    for &(px, py) in &lit_pixels {
+        put(px, py, LABEL_COLOR);
+    }
 }
 
-fn draw_linestring(
-    img: &mut RgbImage,
+/// 5x8 bitmap font for printable ASCII (0x20-0x7e). Each glyph is five
+/// columns, left to right; bit 0 is the top row.
+const FONT_5X8: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // '#'
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x56, 0x20, 0x50], // '&'
+    [0x00, 0x08, 0x07, 0x03, 0x00], // '\''
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // ')'
+    [0x2a, 0x1c, 0x7f, 0x1c, 0x2a], // '*'
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // '+'
+    [0x00, 0x80, 0x70, 0x30, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x00, 0x60, 0x60, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // '0'
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // '1'
+    [0x72, 0x49, 0x49, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x49, 0x4d, 0x33], // '3'
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3c, 0x4a, 0x49, 0x49, 0x31], // '6'
+    [0x41, 0x21, 0x11, 0x09, 0x07], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x46, 0x49, 0x49, 0x29, 0x1e], // '9'
+    [0x00, 0x00, 0x14, 0x00, 0x00], // ':'
+    [0x00, 0x40, 0x34, 0x00, 0x00], // ';'
+    [0x00, 0x08, 0x14, 0x22, 0x41], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x59, 0x09, 0x06], // '?'
+    [0x3e, 0x41, 0x5d, 0x59, 0x4e], // '@'
+    [0x7c, 0x12, 0x11, 0x12, 0x7c], // 'A'
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7f, 0x41, 0x41, 0x41, 0x3e], // 'D'
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3e, 0x41, 0x41, 0x51, 0x73], // 'G'
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // 'H'
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // 'J'
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7f, 0x02, 0x1c, 0x02, 0x7f], // 'M'
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // 'N'
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // 'O'
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // 'Q'
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x26, 0x49, 0x49, 0x49, 0x32], // 'S'
+    [0x03, 0x01, 0x7f, 0x01, 0x03], // 'T'
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // 'U'
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // 'V'
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x03, 0x04, 0x78, 0x04, 0x03], // 'Y'
+    [0x61, 0x59, 0x49, 0x4d, 0x43], // 'Z'
+    [0x00, 0x7f, 0x41, 0x41, 0x41], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x41, 0x7f], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x03, 0x07, 0x08, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x78, 0x40], // 'a'
+    [0x7f, 0x28, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x28], // 'c'
+    [0x38, 0x44, 0x44, 0x28, 0x7f], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x00, 0x08, 0x7e, 0x09, 0x02], // 'f'
+    [0x18, 0xa4, 0xa4, 0x9c, 0x78], // 'g'
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x40, 0x3d, 0x00], // 'j'
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // 'l'
+    [0x7c, 0x04, 0x78, 0x04, 0x78], // 'm'
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0xfc, 0x18, 0x24, 0x24, 0x18], // 'p'
+    [0x18, 0x24, 0x24, 0x18, 0xfc], // 'q'
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x24], // 's'
+    [0x04, 0x04, 0x3f, 0x44, 0x24], // 't'
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // 'u'
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // 'v'
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x4c, 0x90, 0x90, 0x90, 0x7c], // 'y'
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x77, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x02, 0x01, 0x02, 0x04, 0x02], // '~'
+];
+
+/// Draw a geometry of any dimension (Z and M values are ignored). Curves are
+/// already linearized by GDAL (see `main`); types that still cannot be drawn
+/// are counted in `skipped` by name.
+fn draw_geometry(
+    img: &mut RgbaImage,
     geom: &gdal::vector::Geometry,
-    extent: &gdal::vector::Envelope,
-    scale_x: f64,
-    scale_y: f64,
-    img_height: usize,
+    transform: &PixelTransform,
+    style: &VectorStyle,
+    skipped: &mut std::collections::BTreeMap<String, usize>,
+) {
+    use gdal::vector::OGRwkbGeometryType;
+
+    let color = style.line;
+
+    // Fold the Z, M and ZM variants onto the 2D type
+    match gdal::vector::geometry_type_flatten(geom.geometry_type()) {
+        // Points
+        OGRwkbGeometryType::wkbPoint => {
+            if geom.point_count() == 0 {
+                return; // POINT EMPTY
+            }
+            let (x, y, _) = geom.get_point(0);
+            let (px, py) = transform.to_pixel(x, y);
+            draw_point(
+                img,
+                px as i32,
+                py as i32,
+                style.symbol,
+                style.point_size,
+                style.point_color.unwrap_or(color),
+            );
+        }
+        // LineStrings
+        OGRwkbGeometryType::wkbLineString => {
+            draw_linestring(img, geom, transform, style.stroke());
+        }
+        // Polygons (a triangle is a polygon with a single three-sided ring)
+        OGRwkbGeometryType::wkbPolygon | OGRwkbGeometryType::wkbTriangle => {
+            // Fill with even-odd rule so interior rings (holes) stay empty
+            if style.fill_opacity > 0.0 {
+                let mut points = Vec::new();
+                let rings: Vec<Vec<(f64, f64)>> = (0..geom.geometry_count())
+                    .map(|i| {
+                        points.clear();
+                        geom.get_geometry(i).get_points(&mut points);
+                        points
+                            .iter()
+                            .map(|&(x, y, _)| transform.to_pixel(x, y))
+                            .collect()
+                    })
+                    .collect();
+                let fill_color = style.fill_color.unwrap_or(style.line);
+                fill_polygon(img, &rings, fill_color, style.fill_opacity);
+            }
+
+            // Outline exterior and interior rings
+            for i in 0..geom.geometry_count() {
+                let ring = geom.get_geometry(i);
+                draw_linestring(img, &ring, transform, style.stroke());
+            }
+        }
+        // Multi geometries and surfaces made of polygons - recurse
+        OGRwkbGeometryType::wkbMultiPoint
+        | OGRwkbGeometryType::wkbMultiLineString
+        | OGRwkbGeometryType::wkbMultiPolygon
+        | OGRwkbGeometryType::wkbGeometryCollection
+        | OGRwkbGeometryType::wkbPolyhedralSurface
+        | OGRwkbGeometryType::wkbTIN => {
+            for i in 0..geom.geometry_count() {
+                let sub_geom = geom.get_geometry(i);
+                draw_geometry(img, &sub_geom, transform, style, skipped);
+            }
+        }
+        _ if geom.geometry_count() > 0 => {
+            // Try to handle as collection
+            for i in 0..geom.geometry_count() {
+                let sub_geom = geom.get_geometry(i);
+                draw_geometry(img, &sub_geom, transform, style, skipped);
+            }
+        }
+        _ => *skipped.entry(geom.geometry_name()).or_default() += 1,
+    }
+}
+
+/// Scanline fill of a polygon given as rings in pixel coordinates, using the
+/// even-odd rule. A pixel is filled when its center lies inside.
+fn fill_polygon(img: &mut RgbaImage, rings: &[Vec<(f64, f64)>], color: Rgb<u8>, opacity: f64) {
+    let (min_y, max_y) = rings
+        .iter()
+        .flatten()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &(_, y)| {
+            (lo.min(y), hi.max(y))
+        });
+    if !min_y.is_finite() || !max_y.is_finite() {
+        return;
+    }
+
+    let first_row = (min_y - 0.5).ceil().max(0.0) as u32;
+    let last_row = ((max_y - 0.5).floor() as i64).min(img.height() as i64 - 1);
+    let mut crossings = Vec::new();
+
+    for y in first_row as i64..=last_row {
+        let scan_y = y as f64 + 0.5;
+        crossings.clear();
+
+        for ring in rings {
+            if ring.len() < 2 {
+                continue;
+            }
+            // Rings are normally closed, but don't rely on it
+            let closing = (ring[ring.len() - 1], ring[0]);
+            for (&(x0, y0), &(x1, y1)) in ring
+                .iter()
+                .zip(ring.iter().skip(1))
+                .chain(std::iter::once((&closing.0, &closing.1)))
+            {
+                if (y0 <= scan_y) != (y1 <= scan_y) {
+                    crossings.push(x0 + (scan_y - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+        }
+
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        for span in crossings.chunks_exact(2) {
+            let start = (span[0] - 0.5).ceil().max(0.0) as i64;
+            let end = ((span[1] - 0.5).ceil() as i64).min(img.width() as i64);
+            for x in start..end {
+                blend_pixel(img, x as u32, y as u32, color, opacity);
+            }
+        }
+    }
+}
+
+/// Composite `color` at `opacity` over the pixel, so strokes drawn on a
+/// transparent canvas keep their own color while gaining coverage.
+fn blend_pixel(img: &mut RgbaImage, x: u32, y: u32, color: Rgb<u8>, opacity: f64) {
+    let px = img.get_pixel_mut(x, y);
+    let below = px.0[3] as f64 / 255.0 * (1.0 - opacity);
+    let alpha = opacity + below;
+    if alpha <= 0.0 {
+        return;
+    }
+    for c in 0..3 {
+        px.0[c] = ((px.0[c] as f64 * below + color.0[c] as f64 * opacity) / alpha).round() as u8;
+    }
+    px.0[3] = (alpha * 255.0).round() as u8;
+}
+
+fn draw_point(
+    img: &mut RgbaImage,
+    x: i32,
+    y: i32,
+    symbol: PointSymbol,
+    radius: f64,
     color: Rgb<u8>,
+) {
+    let r = radius.round().max(0.0) as i32;
+    // Cross arms get thicker as the symbol grows
+    let arm = r / 4;
+
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let inside = match symbol {
+                PointSymbol::Square => true,
+                PointSymbol::Circle => {
+                    ((dx * dx + dy * dy) as f64) <= (radius + 0.5) * (radius + 0.5)
+                }
+                PointSymbol::Cross => dx.abs() <= arm || dy.abs() <= arm,
+                // Apex up; the half width grows linearly down to the base
+                PointSymbol::Triangle => 2 * dx.abs() <= dy + r,
+            };
+            let (px, py) = (x + dx, y + dy);
+            if inside
+                && px >= 0
+                && py >= 0
+                && (px as u32) < img.width()
+                && (py as u32) < img.height()
+            {
+                img.put_pixel(px as u32, py as u32, color.to_rgba());
+            }
+        }
+    }
+}
+
+fn draw_linestring(
+    img: &mut RgbaImage,
+    geom: &gdal::vector::Geometry,
+    transform: &PixelTransform,
+    stroke: Stroke,
 ) {
     let point_count = geom.point_count();
     if point_count < 2 {
         return;
     }
 
+    if stroke.width > 1.0 {
+        let points: Vec<(f64, f64)> = (0..point_count as i32)
+            .map(|i| {
+                let (x, y, _) = geom.get_point(i);
+                transform.to_pixel(x, y)
+            })
+            .collect();
+        draw_thick_polyline(img, &points, stroke);
+        return;
+    }
+
     for i in 0..(point_count - 1) as i32 {
         let (x1, y1, _) = geom.get_point(i);
         let (x2, y2, _) = geom.get_point(i + 1);
-        let px1 = ((x1 - extent.MinX) * scale_x) as i32;
-        let py1 = (img_height as f64 - (y1 - extent.MinY) * scale_y) as i32;
-        let px2 = ((x2 - extent.MinX) * scale_x) as i32;
-        let py2 = (img_height as f64 - (y2 - extent.MinY) * scale_y) as i32;
-        draw_line(img, px1, py1, px2, py2, color);
+        let (px1, py1) = transform.to_pixel(x1, y1);
+        let (px2, py2) = transform.to_pixel(x2, y2);
+        if stroke.antialias {
+            draw_line_antialiased(img, (px1, py1), (px2, py2), stroke.color);
+        } else {
+            draw_line(img, (px1, py1), (px2, py2), stroke.color);
+        }
+    }
+}
+
+/// Clip a segment to the image rectangle, padded by a pixel so strokes
+/// reaching the border are complete (Liang-Barsky). Returns `None` when the
+/// segment misses the image or has a non-finite endpoint.
+fn clip_segment(
+    p0: (f64, f64),
+    p1: (f64, f64),
+    width: f64,
+    height: f64,
+) -> Option<((f64, f64), (f64, f64))> {
+    if ![p0.0, p0.1, p1.0, p1.1].iter().all(|v| v.is_finite()) {
+        return None;
+    }
+    let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    // Each edge as (p, q): the segment is inside where t * p <= q
+    for (p, q) in [
+        (-dx, p0.0 + 1.0),
+        (dx, width + 1.0 - p0.0),
+        (-dy, p0.1 + 1.0),
+        (dy, height + 1.0 - p0.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((
+        (p0.0 + t0 * dx, p0.1 + t0 * dy),
+        (p0.0 + t1 * dx, p0.1 + t1 * dy),
+    ))
+}
+
+/// Xiaolin Wu's line algorithm: each step shades the two pixels straddling
+/// the ideal line in proportion to their coverage.
+fn draw_line_antialiased(img: &mut RgbaImage, p0: (f64, f64), p1: (f64, f64), color: Rgb<u8>) {
+    let Some((p0, p1)) = clip_segment(p0, p1, img.width() as f64, img.height() as f64) else {
+        return;
+    };
+    // Work with pixel centers on integer coordinates
+    let (mut x0, mut y0) = (p0.0 - 0.5, p0.1 - 0.5);
+    let (mut x1, mut y1) = (p1.0 - 0.5, p1.1 - 0.5);
+
+    // Step along the major axis
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+    if steep {
+        std::mem::swap(&mut x0, &mut y0);
+        std::mem::swap(&mut x1, &mut y1);
+    }
+    if x0 > x1 {
+        std::mem::swap(&mut x0, &mut x1);
+        std::mem::swap(&mut y0, &mut y1);
+    }
+    let gradient = if x1 - x0 == 0.0 {
+        1.0
+    } else {
+        (y1 - y0) / (x1 - x0)
+    };
+
+    let (width, height) = (img.width() as i64, img.height() as i64);
+    let major_len = if steep { height } else { width };
+    let mut plot = |major: i64, minor: i64, coverage: f64| {
+        let (x, y) = if steep {
+            (minor, major)
+        } else {
+            (major, minor)
+        };
+        if coverage > 0.0 && x >= 0 && y >= 0 && x < width && y < height {
+            blend_pixel(img, x as u32, y as u32, color, coverage.min(1.0));
+        }
+    };
+
+    // Only walk the part of the line that can be visible
+    let start = (x0.round() as i64).max(0);
+    let end = (x1.round() as i64).min(major_len - 1);
+    for x in start..=end {
+        let y = y0 + gradient * (x as f64 - x0);
+        let base = y.floor();
+        let frac = y - base;
+        plot(x, base as i64, 1.0 - frac);
+        plot(x, base as i64 + 1, frac);
+    }
+}
+
+/// Stroke a polyline wider than one pixel as a union of capsules: every
+/// pixel whose center lies within half the width of a segment is painted.
+/// Round caps on every segment give round joins for free; coverage is the
+/// maximum over all segments so joins aren't blended twice.
+fn draw_thick_polyline(img: &mut RgbaImage, points: &[(f64, f64)], stroke: Stroke) {
+    let half = stroke.width / 2.0;
+    // Farthest distance from the centerline that can get any coverage
+    let reach = if stroke.antialias { half + 0.5 } else { half };
+    let (width, height) = (img.width() as i64, img.height() as i64);
+    let mut coverage: std::collections::HashMap<(u32, u32), f64> = std::collections::HashMap::new();
+
+    for segment in points.windows(2) {
+        let (p0, p1) = (segment[0], segment[1]);
+        if ![p0.0, p0.1, p1.0, p1.1].iter().all(|v| v.is_finite()) {
+            continue;
+        }
+        let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+        let len_sq = dx * dx + dy * dy;
+
+        let min_y = ((p0.1.min(p1.1) - reach - 0.5).floor() as i64).max(0);
+        let max_y = ((p0.1.max(p1.1) + reach).ceil() as i64).min(height - 1);
+        for y in min_y..=max_y {
+            let cy = y as f64 + 0.5;
+            // Span of the capsule on this row: inside the padded bounding box
+            // and within the band around the segment's supporting line
+            let (mut lo, mut hi) = (p0.0.min(p1.0) - reach, p0.0.max(p1.0) + reach);
+            if dy != 0.0 {
+                let center = p0.0 + (cy - p0.1) * dx / dy;
+                let band = reach * len_sq.sqrt() / dy.abs();
+                lo = lo.max(center - band);
+                hi = hi.min(center + band);
+            }
+            let min_x = ((lo - 0.5).floor() as i64).max(0);
+            let max_x = ((hi - 0.5).ceil() as i64).min(width - 1);
+
+            for x in min_x..=max_x {
+                let cx = x as f64 + 0.5;
+                // Distance from the pixel center to the closest point of the segment
+                let t = if len_sq > 0.0 {
+                    (((cx - p0.0) * dx + (cy - p0.1) * dy) / len_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let dist = (cx - (p0.0 + t * dx)).hypot(cy - (p0.1 + t * dy));
+
+                let value = if stroke.antialias {
+                    (half + 0.5 - dist).clamp(0.0, 1.0)
+                } else if dist <= half {
+                    1.0
+                } else {
+                    0.0
+                };
+                if value > 0.0 {
+                    let entry = coverage.entry((x as u32, y as u32)).or_insert(0.0);
+                    *entry = entry.max(value);
+                }
+            }
+        }
+    }
+
+    for ((x, y), value) in coverage {
+        blend_pixel(img, x, y, stroke.color, value);
     }
 }
 
-fn draw_line(img: &mut RgbImage, x0: i32, y0: i32, x1: i32, y1: i32, color: Rgb<u8>) {
+fn draw_line(img: &mut RgbaImage, p0: (f64, f64), p1: (f64, f64), color: Rgb<u8>) {
+    // Only the visible part is walked, so endpoints far off the image (or
+    // from failed reprojections) can't stall the loop or overflow
+    let Some((p0, p1)) = clip_segment(p0, p1, img.width() as f64, img.height() as f64) else {
+        return;
+    };
+    let (x0, y0) = (p0.0.floor() as i32, p0.1.floor() as i32);
+    let (x1, y1) = (p1.0.floor() as i32, p1.1.floor() as i32);
+
     // Bresenham's line algorithm
     let dx = (x1 - x0).abs();
     let dy = -(y1 - y0).abs();
@@ -930,7 +4892,7 @@ fn draw_line(img: &mut RgbImage, x0: i32, y0: i32, x1: i32, y1: i32, color: Rgb<
 
     loop {
         if x >= 0 && y >= 0 && (x as u32) < img.width() && (y as u32) < img.height() {
-            img.put_pixel(x as u32, y as u32, color);
+            img.put_pixel(x as u32, y as u32, color.to_rgba());
         }
 
         if x == x1 && y == y1 {
@@ -948,3 +4910,422 @@ fn draw_line(img: &mut RgbImage, x0: i32, y0: i32, x1: i32, y1: i32, color: Rgb<
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Contents of the DCS envelopes screen passthrough produced.
+    fn screen_envelopes(wrapped: &str) -> Vec<&str> {
+        wrapped
+            .split("\x1bP")
+            .skip(1)
+            .map(|e| e.strip_suffix("\x1b\\").expect("unterminated envelope"))
+            .collect()
+    }
+
+    #[test]
+    fn passthrough_without_multiplexer_is_unchanged() {
+        let seq = "\x1b_Ga=T;AAAA\x1b\\";
+        assert_eq!(wrap_passthrough(seq, None), seq);
+    }
+
+    #[test]
+    fn tmux_passthrough_doubles_escapes() {
+        assert_eq!(
+            wrap_passthrough("\x1b_Ga=T;AAAA\x1b\\", Some(Multiplexer::Tmux)),
+            "\x1bPtmux;\x1b\x1b_Ga=T;AAAA\x1b\x1b\\\x1b\\"
+        );
+    }
+
+    #[test]
+    fn screen_passthrough_splits_inner_string_terminator() {
+        assert_eq!(
+            wrap_passthrough("\x1b_Gm=0;AAAA\x1b\\", Some(Multiplexer::Screen)),
+            "\x1bP\x1b_Gm=0;AAAA\x1b\x1b\\\x1bP\\\x1b\\"
+        );
+    }
+
+    #[test]
+    fn screen_passthrough_chunks_long_sequences() {
+        let seq = format!(
+            "\x1b_Gm=1;{}\x1b\\\x1b_Gm=0;{}\x1b\\",
+            "A".repeat(2000),
+            "B".repeat(700)
+        );
+        let wrapped = wrap_passthrough(&seq, Some(Multiplexer::Screen));
+        let envelopes = screen_envelopes(&wrapped);
+        assert!(envelopes.len() > 3);
+        for envelope in &envelopes {
+            assert!(envelope.len() <= SCREEN_DCS_CHUNK);
+            assert!(!envelope.contains("\x1b\\"));
+        }
+        assert_eq!(envelopes.concat(), seq);
+    }
+
+    #[test]
+    fn size_report_is_parsed_as_width_height() {
+        assert_eq!(parse_size_report("\x1b[4;600;800t", "4"), Some((800, 600)));
+        assert_eq!(
+            parse_size_report("\x1b[6;20;10t\x1b[?62;22c", "6"),
+            Some((10, 20))
+        );
+    }
+
+    #[test]
+    fn size_report_rejects_other_codes_and_bad_values() {
+        assert_eq!(parse_size_report("\x1b[4;600;800t", "6"), None);
+        assert_eq!(parse_size_report("\x1b[4;0;800t", "4"), None);
+        assert_eq!(parse_size_report("\x1b[4;600;800;1t", "4"), None);
+        assert_eq!(parse_size_report("\x1b[4;abc;800t", "4"), None);
+        assert_eq!(parse_size_report("\x1b[?62;22c", "4"), None);
+        assert_eq!(parse_size_report("", "4"), None);
+    }
+
+    #[test]
+    fn da1_reply_is_detected() {
+        assert!(has_da1_reply(b"\x1b[?64;1;9c"));
+        assert!(has_da1_reply(b"\x1b[4;600;800t\x1b[?62c"));
+        assert!(!has_da1_reply(b"\x1b[4;600;800t"));
+        assert!(!has_da1_reply(b"\x1b[?64;1"));
+    }
+
+    #[test]
+    fn quadrant_glyphs_follow_pixel_order() {
+        // Bits: top-left, top-right, bottom-left, bottom-right
+        assert_eq!(quadrant_glyph(0), ' ');
+        assert_eq!(quadrant_glyph(0b0001), '▘');
+        assert_eq!(quadrant_glyph(0b0011), '▀');
+        assert_eq!(quadrant_glyph(0b0101), '▌');
+        assert_eq!(quadrant_glyph(0b1001), '▚');
+        assert_eq!(quadrant_glyph(0b1111), '█');
+    }
+
+    #[test]
+    fn sextant_glyphs_skip_existing_blocks() {
+        assert_eq!(sextant_glyph(0), ' ');
+        assert_eq!(sextant_glyph(0x15), '▌');
+        assert_eq!(sextant_glyph(0x2a), '▐');
+        assert_eq!(sextant_glyph(0x3f), '█');
+        assert_eq!(sextant_glyph(0x01), '\u{1FB00}');
+        assert_eq!(sextant_glyph(0x14), '\u{1FB13}');
+        assert_eq!(sextant_glyph(0x16), '\u{1FB14}');
+        assert_eq!(sextant_glyph(0x3e), '\u{1FB3B}');
+
+        // The other 60 patterns cover the sextant block exactly once
+        let mut glyphs: Vec<char> = (1..0x3f)
+            .filter(|&m| m != 0x15 && m != 0x2a)
+            .map(sextant_glyph)
+            .collect();
+        glyphs.sort();
+        glyphs.dedup();
+        assert_eq!(glyphs.len(), 60);
+        assert_eq!(glyphs.first(), Some(&'\u{1FB00}'));
+        assert_eq!(glyphs.last(), Some(&'\u{1FB3B}'));
+    }
+
+    #[test]
+    fn braille_glyphs_map_pixels_to_dots() {
+        assert_eq!(braille_glyph(0), '\u{2800}');
+        assert_eq!(braille_glyph(0xff), '⣿');
+        // Top row: dots 1 and 4
+        assert_eq!(braille_glyph(0b0000_0011), '⠉');
+        // Left column: dots 1, 2, 3 and 7
+        assert_eq!(braille_glyph(0b0101_0101), '⡇');
+        // Bottom right: dot 8
+        assert_eq!(braille_glyph(0b1000_0000), '⢀');
+    }
+
+    fn filled(img: &RgbaImage) -> Vec<(u32, u32)> {
+        img.enumerate_pixels()
+            .filter(|(_, _, p)| p.0[3] > 0)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    fn square(min: f64, max: f64) -> Vec<(f64, f64)> {
+        vec![(min, min), (max, min), (max, max), (min, max), (min, min)]
+    }
+
+    #[test]
+    fn polygon_fill_covers_pixel_centers_inside() {
+        let mut img = RgbaImage::new(6, 6);
+        fill_polygon(&mut img, &[square(1.0, 4.0)], Rgb([255, 0, 0]), 1.0);
+        let expected: Vec<(u32, u32)> = (1..4).flat_map(|y| (1..4).map(move |x| (x, y))).collect();
+        assert_eq!(filled(&img), expected);
+        assert_eq!(*img.get_pixel(2, 2), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn polygon_fill_leaves_holes_empty() {
+        let mut img = RgbaImage::new(6, 6);
+        let rings = [square(0.0, 6.0), square(2.0, 4.0)];
+        fill_polygon(&mut img, &rings, Rgb([255, 0, 0]), 1.0);
+        let hole = [(2, 2), (3, 2), (2, 3), (3, 3)];
+        assert_eq!(filled(&img).len(), 36 - hole.len());
+        for (x, y) in hole {
+            assert_eq!(img.get_pixel(x, y).0[3], 0);
+        }
+    }
+
+    #[test]
+    fn polygon_fill_closes_open_rings_and_clips() {
+        let open_ring = vec![(-3.0, 2.0), (3.0, 2.0), (3.0, 9.0), (-3.0, 9.0)];
+        let mut closed_ring = open_ring.clone();
+        closed_ring.push(open_ring[0]);
+
+        let mut open = RgbaImage::new(6, 6);
+        let mut closed = RgbaImage::new(6, 6);
+        fill_polygon(&mut open, &[open_ring], Rgb([0, 0, 255]), 1.0);
+        fill_polygon(&mut closed, &[closed_ring], Rgb([0, 0, 255]), 1.0);
+        assert_eq!(filled(&open), filled(&closed));
+        assert_eq!(filled(&open).len(), 3 * 4);
+    }
+
+    #[test]
+    fn degenerate_polygon_fills_nothing() {
+        let mut img = RgbaImage::new(6, 6);
+        fill_polygon(&mut img, &[square(2.0, 2.0)], Rgb([0, 0, 255]), 1.0);
+        fill_polygon(&mut img, &[Vec::new()], Rgb([0, 0, 255]), 1.0);
+        assert!(filled(&img).is_empty());
+    }
+
+    #[test]
+    fn translucent_fill_composites_over_transparent_pixels() {
+        let mut img = RgbaImage::new(2, 2);
+        fill_polygon(&mut img, &[square(0.0, 2.0)], Rgb([200, 100, 0]), 0.5);
+        assert_eq!(*img.get_pixel(0, 0), Rgba([200, 100, 0, 128]));
+    }
+
+    #[test]
+    fn quantile_breaks_split_sorted_values() {
+        let values: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(quantile_breaks(&values, 5), vec![3.0, 5.0, 7.0, 9.0]);
+        assert_eq!(quantile_breaks(&values, 1), Vec::<f64>::new());
+        assert_eq!(quantile_breaks(&[], 5), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn quantile_breaks_drop_duplicates() {
+        let values = [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0, 3.0];
+        assert_eq!(quantile_breaks(&values, 4), vec![1.0]);
+    }
+
+    #[test]
+    fn equal_interval_breaks_span_the_range() {
+        assert_eq!(
+            equal_interval_breaks(&[0.0, 3.0, 10.0], 5),
+            vec![2.0, 4.0, 6.0, 8.0]
+        );
+        assert_eq!(equal_interval_breaks(&[4.0, 4.0], 5), Vec::<f64>::new());
+        assert_eq!(equal_interval_breaks(&[], 5), Vec::<f64>::new());
+    }
+
+    fn sample_table() -> AttributeTable {
+        use gdal::vector::FieldValue;
+
+        AttributeTable {
+            columns: vec![
+                ("name".to_string(), "String".to_string(), false),
+                ("n".to_string(), "Integer".to_string(), true),
+            ],
+            rows: vec![
+                TableRow {
+                    fid: Some(1),
+                    geom_type: Some("POINT".to_string()),
+                    point_count: 1,
+                    wkt: Some("POINT (1 2)".to_string()),
+                    values: vec![
+                        Some(FieldValue::StringValue("a, \"b\"\nc".to_string())),
+                        Some(FieldValue::IntegerValue(3)),
+                    ],
+                },
+                TableRow {
+                    fid: None,
+                    geom_type: None,
+                    point_count: 0,
+                    wkt: None,
+                    values: vec![None, None],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn csv_export_quotes_special_characters() {
+        assert_eq!(
+            attribute_table_csv(&sample_table()),
+            "fid,geometry_type,wkt,name,n\n\
+             1,POINT,POINT (1 2),\"a, \"\"b\"\"\nc\",3\n\
+             ,,,,\n"
+        );
+    }
+
+    #[test]
+    fn json_export_escapes_strings_and_uses_null() {
+        assert_eq!(
+            attribute_table_json(&sample_table()),
+            "[\n  {\"fid\":1,\"geometry_type\":\"POINT\",\"wkt\":\"POINT (1 2)\",\
+             \"name\":\"a, \\\"b\\\"\\nc\",\"n\":3},\n  \
+             {\"fid\":null,\"geometry_type\":null,\"wkt\":null,\"name\":null,\"n\":null}\n]\n"
+        );
+    }
+
+    #[test]
+    fn json_export_escapes_control_characters_and_non_finite_numbers() {
+        use gdal::vector::FieldValue;
+
+        let table = AttributeTable {
+            columns: vec![
+                ("s\\".to_string(), "String".to_string(), false),
+                ("r".to_string(), "RealList".to_string(), true),
+            ],
+            rows: vec![TableRow {
+                fid: Some(7),
+                geom_type: None,
+                point_count: 0,
+                wkt: None,
+                values: vec![
+                    Some(FieldValue::StringValue("\t\u{1}\r".to_string())),
+                    Some(FieldValue::RealListValue(vec![1.5, f64::NAN])),
+                ],
+            }],
+        };
+        assert_eq!(
+            attribute_table_json(&table),
+            "[\n  {\"fid\":7,\"geometry_type\":null,\"wkt\":null,\
+             \"s\\\\\":\"\\t\\u0001\\r\",\"r\":[1.5,null]}\n]\n"
+        );
+    }
+
+    #[test]
+    fn cells_are_truncated_with_an_ellipsis() {
+        assert_eq!(truncate_cell("short", 10), "short");
+        assert_eq!(truncate_cell("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_cell("much too long", 5), "much…");
+        assert_eq!(truncate_cell("ééééé", 3), "éé…");
+    }
+
+    #[test]
+    fn layer_selection_accepts_lists_and_all() {
+        assert_eq!(parse_layer_selection("all", 3).unwrap(), vec![0, 1, 2]);
+        assert_eq!(parse_layer_selection(" ALL ", 2).unwrap(), vec![0, 1]);
+        assert_eq!(parse_layer_selection("2", 3).unwrap(), vec![2]);
+        assert_eq!(parse_layer_selection("0, 2,1", 3).unwrap(), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn layer_selection_rejects_bad_indices() {
+        assert!(parse_layer_selection("3", 3).is_err());
+        assert!(parse_layer_selection("0,x", 3).is_err());
+        assert!(parse_layer_selection("-1", 3).is_err());
+        assert!(parse_layer_selection("", 3).is_err());
+        assert!(parse_layer_selection("0,,1", 3).is_err());
+    }
+
+    fn assert_close((x, y): (f64, f64), (ex, ey): (f64, f64)) {
+        assert!(
+            (x - ex).abs() < 1e-9 && (y - ey).abs() < 1e-9,
+            "({}, {}) != ({}, {})",
+            x,
+            y,
+            ex,
+            ey
+        );
+    }
+
+    #[test]
+    fn geo_transform_maps_raster_corners_to_output_pixels() {
+        // 50x40 source pixels of 10 map units, rendered at half size
+        let gt = [100.0, 10.0, 0.0, 200.0, 0.0, -10.0];
+        let t = PixelTransform::from_geo_transform(&gt, (50, 40), (25, 20)).unwrap();
+        assert_close(t.to_pixel(100.0, 200.0), (0.0, 0.0));
+        assert_close(t.to_pixel(600.0, -200.0), (25.0, 20.0));
+        assert_close(t.to_pixel(350.0, 0.0), (12.5, 10.0));
+        assert!((t.map_units_per_pixel() - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rotated_geo_transform_round_trips() {
+        let gt = [10.0, 2.0, 0.5, -4.0, 0.25, -1.5];
+        let t = PixelTransform::from_geo_transform(&gt, (100, 80), (100, 80)).unwrap();
+        for (col, row) in [(0.0, 0.0), (100.0, 0.0), (37.5, 61.25)] {
+            let x = gt[0] + col * gt[1] + row * gt[2];
+            let y = gt[3] + col * gt[4] + row * gt[5];
+            assert_close(t.to_pixel(x, y), (col, row));
+            assert_close(t.to_map(col, row), (x, y));
+        }
+    }
+
+    #[test]
+    fn singular_geo_transform_is_rejected() {
+        let gt = [0.0, 1.0, 2.0, 0.0, 0.5, 1.0];
+        assert!(PixelTransform::from_geo_transform(&gt, (10, 10), (10, 10)).is_err());
+    }
+
+    #[test]
+    fn segments_are_clipped_to_the_padded_image() {
+        // Far-off endpoints come back to the border, up to rounding
+        let (p0, p1) = clip_segment((-1e12, 5.0), (1e12, 5.0), 100.0, 50.0).unwrap();
+        assert!((p0.0 + 1.0).abs() < 1e-3 && p0.1 == 5.0);
+        assert!((p1.0 - 101.0).abs() < 1e-3 && p1.1 == 5.0);
+        assert_eq!(
+            clip_segment((10.0, 10.0), (20.0, 30.0), 100.0, 50.0),
+            Some(((10.0, 10.0), (20.0, 30.0)))
+        );
+        assert_eq!(
+            clip_segment((-50.0, -5.0), (-5.0, -50.0), 100.0, 50.0),
+            None
+        );
+        assert_eq!(
+            clip_segment((0.0, 200.0), (100.0, 200.0), 100.0, 50.0),
+            None
+        );
+        assert_eq!(
+            clip_segment((f64::NAN, 0.0), (10.0, 10.0), 100.0, 50.0),
+            None
+        );
+        assert_eq!(
+            clip_segment((0.0, 0.0), (f64::INFINITY, 10.0), 100.0, 50.0),
+            None
+        );
+    }
+
+    #[test]
+    fn hex_colors_are_parsed() {
+        assert_eq!(parse_hex_color("#ff8800").unwrap(), Rgb([255, 136, 0]));
+        assert_eq!(parse_hex_color("00FF7f").unwrap(), Rgb([0, 255, 127]));
+    }
+
+    #[test]
+    fn malformed_hex_colors_are_rejected() {
+        for bad in [
+            "#fff", "#ff88001", "#gg0000", "", "#", "#ééé", "#+f+f+f", "##ff8800", "#aéff0",
+        ] {
+            assert!(parse_hex_color(bad).is_err(), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn flatten_composites_alpha_over_the_background() {
+        let mut img = RgbaImage::new(3, 1);
+        img.put_pixel(0, 0, Rgba([200, 100, 0, 255]));
+        img.put_pixel(2, 0, Rgba([255, 255, 255, 128]));
+        let flat = flatten(&DynamicImage::ImageRgba8(img), Rgb([0, 0, 100]));
+        assert_eq!(*flat.get_pixel(0, 0), Rgb([200, 100, 0]));
+        assert_eq!(*flat.get_pixel(1, 0), Rgb([0, 0, 100]));
+        assert_eq!(*flat.get_pixel(2, 0), Rgb([128, 128, 178]));
+    }
+
+    #[test]
+    fn interior_point_closes_open_rings() {
+        let open = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        assert_eq!(interior_point(&[open]), Some((5.0, 5.0)));
+    }
+
+    #[test]
+    fn interior_point_avoids_holes() {
+        let rings = [square(0.0, 10.0), square(2.0, 6.0)];
+        assert_eq!(interior_point(&rings), Some((8.0, 5.0)));
+    }
+}